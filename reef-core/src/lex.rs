@@ -1,6 +1,62 @@
 use reef_syntax::common::ComparisonOperator;
-use reef_syntax::token::Token;
+use reef_syntax::token::{Span, Token};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Scans `text` into its tokens, along with the span of each token.
+pub fn tokenize(text: &str) -> Result<(Vec<Token<'_>>, Vec<Span>), Vec<ScannerError>> {
+    let mut scanner = Scanner::new(text, 0);
+    scanner.scan()?;
+
+    Ok((scanner.tokens, scanner.spans))
+}
+
+/// Problems found while scanning. The scanner carries on after finding one,
+/// so every error in the text is reported at once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScannerError {
+    /// A `--[[` block comment which is never closed with `]]`. The position
+    /// is where the comment starts.
+    UnterminatedComment { position: Span },
+    /// A string which is never closed with a `"`. The position is where the
+    /// string starts.
+    UnterminatedString { position: Span },
+    /// A number which can't be read, like `1e` with no exponent after it or
+    /// `0x` with no hex digits.
+    MalformedNumber { literal: String, position: Span },
+    /// A character which can't start any token, such as `@`.
+    UnrecognisedCharacter { character: char, position: Span },
+}
+
+impl ScannerError {
+    /// Where in the source code the error is.
+    pub fn position(&self) -> Span {
+        match self {
+            Self::UnterminatedComment { position }
+            | Self::UnterminatedString { position }
+            | Self::MalformedNumber { position, .. }
+            | Self::UnrecognisedCharacter { position, .. } => *position,
+        }
+    }
+
+    /// What went wrong, without the position.
+    pub fn message(&self) -> String {
+        match self {
+            Self::UnterminatedComment { .. } => "unterminated block comment".to_string(),
+            Self::UnterminatedString { .. } => "unterminated string".to_string(),
+            Self::MalformedNumber { literal, .. } => format!("malformed number `{}`", literal),
+            Self::UnrecognisedCharacter { character, .. } => {
+                format!("unrecognised character `{}`", character)
+            }
+        }
+    }
+}
+
+impl Display for ScannerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "at {}, {}", self.position(), self.message())
+    }
+}
 
 /// Scanner is responsible for converting text input into a stream of tokens
 /// which represent the smallest components of a program. It is a struct so
@@ -9,11 +65,23 @@ use std::collections::HashMap;
 #[derive(Debug, Clone)]
 pub struct Scanner<'a> {
     pub tokens: Vec<Token<'a>>,
+    /// Where each token in `tokens` starts, stored at the same index.
+    pub spans: Vec<Span>,
+    /// Every error found so far. Scanning carries on past them.
+    errors: Vec<ScannerError>,
     text: &'a str,
+    /// Every character in `text` paired with its byte offset. Indexing into
+    /// this is constant time, unlike walking `text.chars()` every time.
+    chars: Vec<(usize, char)>,
     current: usize,
-    line: i32,
+    line: u32,
+    col: u32,
+    /// Where the token currently being scanned started.
+    start: Span,
     keywords: HashMap<&'a str, &'a str>,
     debug: u8,
+    /// Whether whitespace is kept as tokens. See `tokens_with_trivia`.
+    keep_trivia: bool,
 }
 
 impl<'a> Scanner<'a> {
@@ -38,18 +106,26 @@ impl<'a> Scanner<'a> {
         keyword_map.insert("not", "not");
         keyword_map.insert("and", "and");
         keyword_map.insert("var", "var");
+        keyword_map.insert("const", "const");
         keyword_map.insert("log", "log");
+        keyword_map.insert("logerr", "logerr");
         keyword_map.insert("do", "do");
         keyword_map.insert("if", "if");
         keyword_map.insert("or", "or");
 
         Self {
             text,
+            chars: text.char_indices().collect(),
             tokens: vec![],
+            spans: vec![],
+            errors: vec![],
             current: 0,
             line: 1,
+            col: 1,
+            start: Span::default(),
             keywords: keyword_map,
             debug,
+            keep_trivia: false,
         }
     }
 
@@ -60,26 +136,77 @@ impl<'a> Scanner<'a> {
 
     /// Scan the input text and break it down into the smallest components.
     /// Token definitions can be found in ./lib.rs
-    pub fn scan(&mut self) {
-        while self.current < self.text.len() {
+    /// Scanning doesn't stop at an error, so that every error in the text
+    /// can be reported at once. The last token is always a single
+    /// `EndOfFile`, even for empty text.
+    pub fn scan(&mut self) -> Result<(), Vec<ScannerError>> {
+        while self.current < self.chars.len() {
+            self.next_token();
+        }
+
+        // With no characters left, this adds the `EndOfFile` token.
+        if self.tokens.last() != Some(&Token::EndOfFile) {
             self.next_token();
         }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+
+    /// Scans the text like `scan`, but also keeps runs of whitespace as
+    /// `Whitespace` tokens in between the other tokens. This is for tools like
+    /// a formatter that need to put the layout back. The parser skips over
+    /// them, so the tokens can still be parsed.
+    pub fn tokens_with_trivia(mut self) -> Result<(Vec<Token<'a>>, Vec<Span>), Vec<ScannerError>> {
+        self.keep_trivia = true;
+        self.scan()?;
+
+        Ok((self.tokens, self.spans))
+    }
+
+    /// Turns the scanner into an iterator which scans one token at a time,
+    /// for tools which don't need every token up front.
+    pub fn tokens_iter(self) -> TokenIter<'a> {
+        TokenIter {
+            scanner: self,
+            yielded: 0,
+            reached_end: false,
+        }
     }
 
     fn next_token(&mut self) {
+        self.start = Span {
+            line: self.line,
+            col: self.col,
+            start: self.byte_offset(self.current),
+            end: 0,
+        };
+
         match self.get_current_char() {
             Some(c) => match c {
-                '\n' => {
-                    self.line += 1;
-                    self.current += 1;
-                }
-                'a'..='z' | 'A'..='Z' | '_' => self.scan_ident(),
+                c if c.is_alphabetic() || c == '_' => self.scan_ident(),
                 '0'..='9' => self.scan_number(),
                 '"' => self.scan_string(),
                 '-' => self.handle_hyphen(),
-                '+' | '*' | '/' => {
-                    self.tokens.push(Token::BinaryOperator(c));
+                '+' | '*' | '/' | '%' => {
+                    self.advance();
+                    self.add_token(Token::BinaryOperator(c));
+                }
+                '&' | '|' | '^' => {
                     self.advance();
+                    self.add_token(Token::BitwiseOperator(match c {
+                        '&' => "&",
+                        '|' => "|",
+                        _ => "^",
+                    }));
+                }
+                '<' | '>' if self.peek_char(1) == Some(c) => {
+                    self.advance();
+                    self.advance();
+                    self.add_token(Token::BitwiseOperator(if c == '<' { "<<" } else { ">>" }));
                 }
                 '<' | '>' => {
                     self.advance();
@@ -87,22 +214,21 @@ impl<'a> Scanner<'a> {
                     let mut buf = String::new();
                     buf.push(c);
 
-                    match self.get_current_char() {
-                        Some('=') => buf.push('='),
-                        _ => {}
+                    if let Some('=') = self.get_current_char() {
+                        self.advance();
+                        buf.push('=');
                     }
 
                     match buf.as_str() {
-                        "<" => self
-                            .tokens
-                            .push(Token::ComparisonOperator(ComparisonOperator::LessThan)),
+                        "<" => {
+                            self.add_token(Token::ComparisonOperator(ComparisonOperator::LessThan))
+                        }
                         ">" => self
-                            .tokens
-                            .push(Token::ComparisonOperator(ComparisonOperator::GreaterThan)),
-                        "<=" => self.tokens.push(Token::ComparisonOperator(
+                            .add_token(Token::ComparisonOperator(ComparisonOperator::GreaterThan)),
+                        "<=" => self.add_token(Token::ComparisonOperator(
                             ComparisonOperator::LessThanOrEqualTo,
                         )),
-                        ">=" => self.tokens.push(Token::ComparisonOperator(
+                        ">=" => self.add_token(Token::ComparisonOperator(
                             ComparisonOperator::GreaterThanOrEqualTo,
                         )),
                         _ => {}
@@ -113,50 +239,100 @@ impl<'a> Scanner<'a> {
                     match self.get_current_char() {
                         Some('=') => {
                             self.advance();
-                            self.tokens
-                                .push(Token::ComparisonOperator(ComparisonOperator::EqualTo))
+                            self.add_token(Token::ComparisonOperator(ComparisonOperator::EqualTo))
                         }
-                        _ => self.tokens.push(Token::Equals),
+                        _ => self.add_token(Token::Equals),
                     }
                 }
                 '!' => {
                     self.advance();
-                    dbg!(self.get_current_char());
                     match self.get_current_char() {
                         Some('=') => {
                             self.advance();
-                            self.tokens
-                                .push(Token::ComparisonOperator(ComparisonOperator::NotEqualTo))
+                            self.add_token(Token::ComparisonOperator(
+                                ComparisonOperator::NotEqualTo,
+                            ))
                         }
-                        _ => panic!("Expected an equals to follow an exclamation mark"),
+                        // `!` is only used in `!=`. Negation is `not`.
+                        _ => self.unrecognised_character(c),
                     }
                 }
-                '.' | ',' | ';' | ':' | '{' | '}' | '(' | ')' => {
-                    self.tokens.push(Token::Delimiter(c));
-                    self.advance();
-                }
-                c if c.is_whitespace() => {
+                '.' | ',' | ';' | ':' | '{' | '}' | '(' | ')' | '[' | ']' => {
                     self.advance();
+                    self.add_token(Token::Delimiter(c));
                 }
+                c if c.is_whitespace() => self.scan_whitespace(),
                 _ => {
-                    panic!("Panic: Unrecognised character {}", c);
+                    self.advance();
+                    self.unrecognised_character(c);
                 }
             },
-            None => self.tokens.push(Token::EndOfFile),
+            None => self.add_token(Token::EndOfFile),
         }
     }
 
+    /// Records an error for `character`, which has already been advanced past.
+    fn unrecognised_character(&mut self, character: char) {
+        self.errors.push(ScannerError::UnrecognisedCharacter {
+            character,
+            position: Span {
+                end: self.byte_offset(self.current),
+                ..self.start
+            },
+        });
+    }
+
+    /// Pushes `token` to the token list, remembering where it started. This
+    /// should be called once the whole token has been advanced past, so that
+    /// the end of its span is correct.
+    fn add_token(&mut self, token: Token<'a>) {
+        self.tokens.push(token);
+        self.spans.push(Span {
+            end: self.byte_offset(self.current),
+            ..self.start
+        });
+    }
+
     /// Check an identifier against the built-in hashmap of keywords, and returns true if it matches a keyword, else returns false.
     fn is_keyword(&self, ident: &str) -> bool {
         self.keywords.contains_key(ident)
     }
 
     fn get_current_char(&self) -> Option<char> {
-        self.text.chars().nth(self.current)
+        self.chars.get(self.current).map(|&(_, c)| c)
+    }
+
+    /// Looks at the char `offset` places after the current one, without
+    /// advancing.
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.current + offset).map(|&(_, c)| c)
     }
 
-    /// Increment the current char pointer and return the new value.
+    /// Converts a char index into the byte offset of that char in `text`, so
+    /// that `text` can be sliced. An index past the end gives the text length.
+    fn byte_offset(&self, index: usize) -> usize {
+        match self.chars.get(index) {
+            Some(&(offset, _)) => offset,
+            None => self.text.len(),
+        }
+    }
+
+    /// Slices the source text between two char indices.
+    fn slice(&self, start: usize, end: usize) -> &'a str {
+        &self.text[self.byte_offset(start)..self.byte_offset(end)]
+    }
+
+    /// Increment the current char pointer and return the new value. Keeps
+    /// the line and column up to date with the character being passed over.
     fn advance(&mut self) -> usize {
+        match self.get_current_char() {
+            Some('\n') => {
+                self.line += 1;
+                self.col = 1;
+            }
+            _ => self.col += 1,
+        }
+
         self.current += 1;
         self.current
     }
@@ -171,34 +347,118 @@ impl<'a> Scanner<'a> {
         match self.get_current_char() {
             Some(c) => match c {
                 '-' => self.scan_comment(),
-                _ => self.tokens.push(Token::BinaryOperator('-')),
+                _ => self.add_token(Token::BinaryOperator('-')),
             },
-            None => self.tokens.push(Token::BinaryOperator('-')),
+            None => self.add_token(Token::BinaryOperator('-')),
         }
     }
 
-    /// Scan characters that make up an int/float and convert it into a 64 bit
-    /// floating point number. This method can panic if there are multiple "."
+    /// Scan characters that make up an int/float. Numbers can have an exponent
+    /// (`2.5e-3`), or be written in hex (`0xFF`). A number with more than one
+    /// `.`, or an exponent or hex prefix without any digits after it, is
+    /// recorded as malformed. An `_` on its own doesn't count as a digit.
     fn scan_number(&mut self) {
         let start = self.current;
 
+        if let (Some('0'), Some('x' | 'X')) = (self.get_current_char(), self.peek_char(1)) {
+            return self.scan_hex_number(start);
+        }
+
+        let mut dots = 0;
         while let Some(c) = self.get_current_char() {
             match c {
                 c if c.is_ascii_digit() => self.advance(),
-                '_' | '.' => self.advance(),
+                '_' => self.advance(),
+                '.' => {
+                    dots += 1;
+                    self.advance()
+                }
+                _ => break,
+            };
+        }
+
+        if let Some('e' | 'E') = self.get_current_char() {
+            self.advance();
+
+            if let Some('+' | '-') = self.get_current_char() {
+                self.advance();
+            }
+
+            let mut has_digits = false;
+            while let Some(c) = self.get_current_char() {
+                match c {
+                    c if c.is_ascii_digit() => {
+                        has_digits = true;
+                        self.advance()
+                    }
+                    '_' => self.advance(),
+                    _ => break,
+                };
+            }
+
+            if !has_digits {
+                return self.malformed_number(start);
+            }
+        }
+
+        if dots > 1 {
+            return self.malformed_number(start);
+        }
+
+        let sym = self.slice(start, self.current);
+
+        self.add_token(Token::Number(sym));
+    }
+
+    /// Scans a hex number such as `0xFF`, starting from the `0`.
+    fn scan_hex_number(&mut self, start: usize) {
+        // Consume the `0x`
+        self.advance();
+        self.advance();
+
+        let mut has_digits = false;
+        while let Some(c) = self.get_current_char() {
+            match c {
+                c if c.is_ascii_hexdigit() => {
+                    has_digits = true;
+                    self.advance()
+                }
+                '_' => self.advance(),
                 _ => break,
             };
         }
 
-        let sym = &self.text[start..self.current];
+        if !has_digits {
+            return self.malformed_number(start);
+        }
+
+        let sym = self.slice(start, self.current);
 
-        self.tokens.push(Token::Number(sym));
+        self.add_token(Token::Number(sym));
     }
 
-    /// Save the contents of a comment as a string for potential use in the parser.
+    /// Records an error for the number starting at `start`, instead of
+    /// adding a token for it.
+    fn malformed_number(&mut self, start: usize) {
+        self.errors.push(ScannerError::MalformedNumber {
+            literal: self.slice(start, self.current).to_string(),
+            position: Span {
+                end: self.byte_offset(self.current),
+                ..self.start
+            },
+        });
+    }
+
+    /// Scans a comment into a `Comment` token, so tools like a formatter can
+    /// keep it. The parser skips over it.
+    /// A comment starting with `--[[` is a block comment instead.
     fn scan_comment(&mut self) {
         // Capture both hyphens at the start
-        // let start = self.current - 1;
+        let start = self.current - 1;
+
+        if let (Some('['), Some('[')) = (self.peek_char(1), self.peek_char(2)) {
+            return self.scan_block_comment(start);
+        }
 
         while let Some(c) = self.get_current_char() {
             match c {
@@ -207,35 +467,84 @@ impl<'a> Scanner<'a> {
             };
         }
 
-        // Removed for sake of simplicity in the parser. Might add this back later :3
-        // let sym = &self.text[start..self.current];
-        // self.tokens.push(Token::Comment(sym));
+        let sym = self.slice(start, self.current);
+        self.add_token(Token::Comment(sym));
+    }
+
+    /// Skips a run of whitespace, newlines included. The run is only kept as
+    /// a token when scanning with trivia.
+    fn scan_whitespace(&mut self) {
+        let start = self.current;
+
+        while let Some(c) = self.get_current_char() {
+            match c {
+                c if c.is_whitespace() => self.advance(),
+                _ => break,
+            };
+        }
+
+        if self.keep_trivia {
+            let sym = self.slice(start, self.current);
+            self.add_token(Token::Whitespace(sym));
+        }
+    }
+
+    /// Scans a `--[[ ... ]]` block comment, which can span multiple lines.
+    /// Block comments don't nest, so the first `]]` ends the comment. If the
+    /// text ends before the comment is closed, an error is recorded.
+    fn scan_block_comment(&mut self, start: usize) {
+        // Consume the second hyphen and both opening brackets
+        for _ in 0..3 {
+            self.advance();
+        }
+
+        while let Some(c) = self.get_current_char() {
+            if c == ']' && self.peek_char(1) == Some(']') {
+                self.advance();
+                self.advance();
+
+                let sym = self.slice(start, self.current);
+                self.add_token(Token::Comment(sym));
+                return;
+            }
+
+            self.advance();
+        }
+
+        self.errors.push(ScannerError::UnterminatedComment {
+            position: Span {
+                end: self.byte_offset(self.current),
+                ..self.start
+            },
+        });
     }
 
     /// Scans user defined identifiers, or if the identifier matches the name
-    /// of a keyword, return a keyword token instead.
+    /// of a keyword, return a keyword token instead. Identifiers can use
+    /// letters from any language, like `café`, but not symbols such as emoji.
     fn scan_ident(&mut self) {
         let start = self.current;
 
         while let Some(c) = self.get_current_char() {
             match c {
-                c if c.is_ascii_alphanumeric() => self.advance(),
+                c if c.is_alphanumeric() => self.advance(),
                 '_' => self.advance(),
                 _ => break,
             };
         }
 
-        let sym = &self.text[start..self.current];
+        let sym = self.slice(start, self.current);
 
         if self.is_keyword(sym) {
-            self.tokens.push(Token::Keyword(sym))
+            self.add_token(Token::Keyword(sym))
         } else {
-            self.tokens.push(Token::Identifier(sym))
+            self.add_token(Token::Identifier(sym))
         }
     }
 
     /// Scans a string. A string starts and ends with a double quote, with the
-    /// text in between them.
+    /// text in between them. Strings can span several lines, and keep the
+    /// line breaks in them.
     fn scan_string(&mut self) {
         // Consume the first double quote
         self.advance();
@@ -248,10 +557,64 @@ impl<'a> Scanner<'a> {
             };
         }
 
+        if self.get_current_char().is_none() {
+            self.errors.push(ScannerError::UnterminatedString {
+                position: Span {
+                    end: self.byte_offset(self.current),
+                    ..self.start
+                },
+            });
+            return;
+        }
+
         // Consume the ending double quote
         self.advance();
-        let sym = &self.text[start..self.current - 1];
+        let sym = self.slice(start, self.current - 1);
+
+        self.add_token(Token::String(sym));
+    }
+}
+
+/// Scans lazily, one token per call to `next`, rather than all at once like
+/// `Scanner::scan`. The last token is `EndOfFile`, after which `None` is
+/// returned. Errors don't stop the iterator, and can be checked with `errors`.
+#[derive(Debug, Clone)]
+pub struct TokenIter<'a> {
+    scanner: Scanner<'a>,
+    /// How many of the scanner's tokens have been handed out.
+    yielded: usize,
+    /// Whether the end of the text has been reached and `EndOfFile` added.
+    reached_end: bool,
+}
+
+impl<'a> TokenIter<'a> {
+    /// Every error found so far.
+    pub fn errors(&self) -> &[ScannerError] {
+        &self.scanner.errors
+    }
+}
+
+impl<'a> Iterator for TokenIter<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let scanner = &mut self.scanner;
+
+        // Some characters, like whitespace, don't make a token, so keep going
+        // until one does.
+        while self.yielded == scanner.tokens.len() {
+            if self.reached_end {
+                return None;
+            }
+
+            if scanner.current >= scanner.chars.len() {
+                self.reached_end = true;
+            }
+
+            scanner.next_token();
+        }
 
-        self.tokens.push(Token::String(sym));
+        self.yielded += 1;
+        Some(scanner.tokens[self.yielded - 1])
     }
 }
@@ -1,6 +1,20 @@
 pub mod lex;
 pub mod parse;
 
+use reef_syntax::ast::Stmt;
+
+/// Scans and parses `source_code` into the statements making up the program.
+/// Source code with nothing but whitespace in it is an empty program. Only the
+/// first scanner error is reported.
+pub fn parse_to_ast(source_code: &str) -> Result<Vec<Stmt>, parse::ParserError> {
+    let (tokens, spans) = lex::tokenize(source_code).map_err(|mut errors| errors.remove(0))?;
+
+    let mut parser = parse::Parser::new(tokens, spans, 0);
+    parser.parse_all()?;
+
+    Ok(parser.program)
+}
+
 /// A small test suite to quickly test the functions of the lexer and
 /// parser. `test_lexer_and_parser` is a util function that creates a
 /// lexer with `source_code`, and then scans the code, then makes a
@@ -8,13 +22,21 @@ pub mod parse;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use reef_syntax::ast::{Expr, UnaryOperation};
+    use reef_syntax::common::ComparisonOperator;
+    use reef_syntax::json;
+    use reef_syntax::token::{Token, TokenDisplay};
 
-    fn test_lexer_and_parser(source_code: &str) {
+    fn parse_source(source_code: &str) -> Result<(), parse::ParserError> {
         let mut l = lex::Scanner::new(source_code, 1);
-        l.scan();
+        l.scan().unwrap();
+
+        let mut p = parse::Parser::new(l.tokens, l.spans, 1);
+        p.parse_all()
+    }
 
-        let mut p = parse::Parser::new(l.tokens, 1);
-        let res = p.parse_all();
+    fn test_lexer_and_parser(source_code: &str) {
+        let res = parse_source(source_code);
 
         if res.is_err() {
             println!("=-=- Source Code -=-=");
@@ -30,7 +52,932 @@ mod tests {
     }
 
     #[test]
+    #[should_panic]
     fn should_fail() {
         test_lexer_and_parser("var = 10");
     }
+
+    #[test]
+    fn parses_return() {
+        test_lexer_and_parser("{ return 10; }");
+        test_lexer_and_parser("{ return; }");
+    }
+
+    #[test]
+    fn parses_empty_blocks() {
+        test_lexer_and_parser("{ }");
+        test_lexer_and_parser("{ { } }");
+    }
+
+    #[test]
+    fn reports_unclosed_block_opener() {
+        let res = parse_source("log 1;\nlog 2;\n{\n    log 3;\n");
+
+        match res {
+            Err(parse::ParserError::UnclosedDelimiter {
+                delimiter: '{',
+                position,
+            }) => assert_eq!((position.line, position.col), (3, 1)),
+            _ => panic!("Expected an unclosed delimiter error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn reports_unclosed_group_opener() {
+        let res = parse_source("var x =\n(1 + 2");
+
+        match res {
+            Err(parse::ParserError::UnclosedDelimiter {
+                delimiter: '(',
+                position,
+            }) => assert_eq!((position.line, position.col), (2, 1)),
+            _ => panic!("Expected an unclosed delimiter error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn tokens_record_line_and_column() {
+        let mut l = lex::Scanner::new("var x = 1;\n  log x;", 0);
+        l.scan().unwrap();
+
+        assert_eq!((l.spans[0].line, l.spans[0].col), (1, 1));
+        assert_eq!((l.spans[3].line, l.spans[3].col), (1, 9));
+        assert_eq!((l.spans[5].line, l.spans[5].col), (2, 3));
+        assert_eq!((l.spans[6].line, l.spans[6].col), (2, 7));
+    }
+
+    #[test]
+    fn syntax_errors_report_line_and_column() {
+        let res = parse_source("var x = 1;\nvar y = 2 3;");
+
+        match res {
+            Err(parse::ParserError::ExpectedToken { position, .. }) => {
+                assert_eq!((position.line, position.col), (2, 11));
+                assert_eq!(position.to_string(), "line 2, column 11");
+            }
+            _ => panic!("Expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn identifiers_and_booleans_start_comparisons() {
+        for source_code in ["x < 5", "flag == true", "true == flag", "false != x"] {
+            match parse_single_expression(source_code) {
+                Ok(Expr::ComparisonExpression { .. }) => {}
+                res => panic!("Expected a comparison for {}, got {:?}", source_code, res),
+            }
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        for source_code in ["a and b or c", "c or a and b"] {
+            match parse_single_expression(source_code) {
+                Ok(Expr::ComparisonExpression {
+                    lhs,
+                    rhs,
+                    operator: ComparisonOperator::Or,
+                }) => {
+                    let and = if source_code.starts_with('a') {
+                        lhs
+                    } else {
+                        rhs
+                    };
+                    assert!(
+                        matches!(
+                            *and,
+                            Expr::ComparisonExpression {
+                                operator: ComparisonOperator::And,
+                                ..
+                            }
+                        ),
+                        "{:?}",
+                        and
+                    );
+                }
+                res => panic!("Expected an `or` for {}, got {:?}", source_code, res),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_semicolon_reports_what_was_found() {
+        match parse_source("var x = 1\nlog x;") {
+            Err(parse::ParserError::ExpectedToken {
+                expected,
+                found,
+                position,
+            }) => {
+                assert_eq!(expected, Token::Delimiter(';').to_string());
+                assert_eq!(found, Token::Keyword("log").to_string());
+                assert_eq!((position.line, position.col), (2, 1));
+            }
+            res => panic!("Expected an ExpectedToken error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn code_ending_mid_statement_is_incomplete() {
+        for source_code in ["var", "var x =", "var x = 1", "log 1 +", "x = -"] {
+            match parse_source(source_code) {
+                Err(err @ parse::ParserError::UnexpectedEof { .. }) => {
+                    assert!(err.is_incomplete())
+                }
+                res => panic!(
+                    "Expected the end of the code for {}, got {:?}",
+                    source_code, res
+                ),
+            }
+        }
+
+        assert!(!parse_source("var 1;").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn missing_semicolon_at_the_end_is_named() {
+        let err = parse_source("var x = 5").unwrap_err();
+
+        match &err {
+            parse::ParserError::UnexpectedEof {
+                expected: Some(expected),
+                position,
+            } => {
+                assert_eq!(*expected, Token::Delimiter(';').to_string());
+                assert_eq!((position.line, position.col), (1, 9));
+            }
+            res => panic!("Expected the end of the code, got {:?}", res),
+        }
+        assert!(err.is_incomplete());
+        assert_eq!(
+            err.to_string(),
+            "Syntax error: at line 1, column 9, expected Delimiter(';') but the code ended before the statement was finished"
+        );
+    }
+
+    #[test]
+    fn parses_structs_and_field_access() {
+        test_lexer_and_parser(
+            "struct Point { x, y } var p = Point { x = 1, y = 2 }; log p.x, p.y;",
+        );
+        test_lexer_and_parser("struct Empty {} var e = Empty {};");
+
+        match parse_single_expression("shapes[0].corner.x") {
+            Ok(Expr::FieldAccess { target, field }) => {
+                assert_eq!(field, "x");
+                assert!(matches!(*target, Expr::FieldAccess { .. }));
+            }
+            res => panic!("Expected a field access, got {:?}", res),
+        }
+
+        for source_code in [
+            "struct Point { x, x }",
+            "var p = Point { x = 1, x = 2 };",
+            "var x = p.1;",
+        ] {
+            assert!(
+                matches!(
+                    parse_source(source_code),
+                    Err(parse::ParserError::SyntaxError { .. })
+                ),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn loops_need_do_before_their_body() {
+        for source_code in ["for (true) { }", "for (var i = 0; i < 3; i = i + 1) { }"] {
+            match parse_source(source_code) {
+                Err(parse::ParserError::ExpectedToken {
+                    expected, found, ..
+                }) => {
+                    assert_eq!(expected, Token::Keyword("do").to_string());
+                    assert_eq!(found, Token::Delimiter('{').to_string());
+                }
+                res => panic!("Expected a missing `do` for {}, got {:?}", source_code, res),
+            }
+        }
+    }
+
+    #[test]
+    fn chained_comparisons_are_rejected() {
+        match parse_source("var x = 1 < 2 < 3;") {
+            Err(parse::ParserError::SyntaxError { position, message }) => {
+                assert_eq!((position.line, position.col), (1, 15));
+                assert!(message.contains("can't be chained"), "{}", message);
+            }
+            res => panic!("Expected a syntax error, got {:?}", res),
+        }
+
+        // Parentheses or `and` make the meaning clear.
+        test_lexer_and_parser("var x = (1 < 2) == true;");
+        test_lexer_and_parser("var x = 1 < 2 and 2 < 3;");
+    }
+
+    /// Scans roughly 1MB of source code. Scanning used to walk the text from
+    /// the start for every character, which made this take minutes.
+    #[test]
+    fn scans_large_input_quickly() {
+        let source_code = "var abc = 123.45; log \"hello\", abc; -- comment\n".repeat(25_000);
+        assert!(source_code.len() >= 1_000_000);
+
+        let start = std::time::Instant::now();
+        let mut l = lex::Scanner::new(&source_code, 0);
+        l.scan().unwrap();
+
+        assert_eq!(l.tokens.len(), 25_000 * 11 + 1);
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(5),
+            "Scanning 1MB took {:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn spans_record_byte_offsets() {
+        let (tokens, spans) = lex::tokenize("\"é\" <= x;").unwrap();
+
+        assert_eq!(tokens[0], Token::String("é"));
+        assert_eq!((spans[0].start, spans[0].end), (0, 4));
+        assert_eq!((spans[1].start, spans[1].end), (5, 7));
+        assert_eq!(tokens[2], Token::Identifier("x"));
+        assert_eq!((spans[2].start, spans[2].end), (8, 9));
+        assert_eq!((spans[3].start, spans[3].end), (9, 10));
+    }
+
+    #[test]
+    fn identifiers_and_strings_can_be_unicode() {
+        let source_code = "var café = \"🐟 fish\"; log café;";
+        let (tokens, spans) = lex::tokenize(source_code).unwrap();
+
+        assert_eq!(tokens[1], Token::Identifier("café"));
+        assert_eq!(tokens[3], Token::String("🐟 fish"));
+        assert_eq!(&source_code[spans[3].start..spans[3].end], "\"🐟 fish\"");
+        assert_eq!(tokens[6], Token::Identifier("café"));
+        // Columns count characters, not bytes.
+        assert_eq!((spans[6].line, spans[6].col), (1, 26));
+
+        // Symbols still aren't allowed outside of strings.
+        assert!(lex::tokenize("var 🐟 = 1;").is_err());
+    }
+
+    #[test]
+    fn doubled_angle_brackets_are_shifts() {
+        let (tokens, _) = lex::tokenize("1 << 2 >> 3 <= 4 & 5").unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number("1"),
+                Token::BitwiseOperator("<<"),
+                Token::Number("2"),
+                Token::BitwiseOperator(">>"),
+                Token::Number("3"),
+                Token::ComparisonOperator(ComparisonOperator::LessThanOrEqualTo),
+                Token::Number("4"),
+                Token::BitwiseOperator("&"),
+                Token::Number("5"),
+                Token::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn scans_square_brackets_as_delimiters() {
+        let (tokens, _) = lex::tokenize("[1, 2]").unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Delimiter('['),
+                Token::Number("1"),
+                Token::Delimiter(','),
+                Token::Number("2"),
+                Token::Delimiter(']'),
+                Token::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_span_multiple_lines() {
+        let mut l = lex::Scanner::new("--[[ one\ntwo\nthree ]] log x;", 0);
+        l.scan().unwrap();
+
+        assert_eq!(l.tokens[0], Token::Comment("--[[ one\ntwo\nthree ]]"));
+        assert_eq!(l.tokens[1], Token::Keyword("log"));
+        assert_eq!((l.spans[1].line, l.spans[1].col), (3, 10));
+    }
+
+    /// Block comments don't nest, so the first `]]` closes the comment and
+    /// anything after it is code again.
+    #[test]
+    fn block_comments_end_at_the_first_closer() {
+        let mut l = lex::Scanner::new("--[[ outer --[[ inner ]] log x; -- ]]", 0);
+        l.scan().unwrap();
+
+        assert_eq!(
+            l.tokens,
+            vec![
+                Token::Comment("--[[ outer --[[ inner ]]"),
+                Token::Keyword("log"),
+                Token::Identifier("x"),
+                Token::Delimiter(';'),
+                Token::Comment("-- ]]"),
+                Token::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_an_error() {
+        let mut l = lex::Scanner::new("log 1;\n--[[ never closed\nlog 2;", 0);
+        let errors = l.scan().unwrap_err();
+
+        assert_eq!(l.tokens.len(), 4);
+        match errors.as_slice() {
+            [lex::ScannerError::UnterminatedComment { position }] => {
+                assert_eq!((position.line, position.col), (2, 1))
+            }
+            errors => panic!("Expected an unterminated comment, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        let mut l = lex::Scanner::new("log 1;\nlog \"never closed;\nlog 2;", 0);
+        let errors = l.scan().unwrap_err();
+
+        assert_eq!(l.tokens.len(), 5);
+        match errors.as_slice() {
+            [err @ lex::ScannerError::UnterminatedString { position }] => {
+                assert_eq!((position.line, position.col), (2, 5));
+                assert_eq!(err.to_string(), "at line 2, column 5, unterminated string");
+            }
+            errors => panic!("Expected an unterminated string, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn strings_can_span_lines() {
+        let (tokens, spans) = lex::tokenize("log \"one\ntwo\";\nlog 3;").unwrap();
+
+        assert_eq!(tokens[1], Token::String("one\ntwo"));
+        // The line count carries on after the string.
+        assert_eq!(tokens[4], Token::Number("3"));
+        assert_eq!((spans[4].line, spans[4].col), (3, 5));
+    }
+
+    #[test]
+    fn iterating_the_scanner_matches_scan() {
+        let source_code = "var x = [1, \"a b\"];\n-- note\nif (x[0] >= 1) then { log x; }";
+        let (tokens, _) = lex::tokenize(source_code).unwrap();
+
+        let mut iter = lex::Scanner::new(source_code, 0).tokens_iter();
+        let iterated: Vec<Token> = iter.by_ref().collect();
+
+        assert_eq!(iterated, tokens);
+        assert_eq!(iter.next(), None);
+        assert!(iter.errors().is_empty());
+
+        let mut iter = lex::Scanner::new("log @;", 0).tokens_iter();
+        assert_eq!(iter.by_ref().count(), 3);
+        assert_eq!(iter.errors().len(), 1);
+    }
+
+    #[test]
+    fn trivia_keeps_comments_and_blank_lines() {
+        let source_code = "var x = 1;\n\n-- note\nlog x;";
+        let (tokens, spans) = lex::Scanner::new(source_code, 0)
+            .tokens_with_trivia()
+            .unwrap();
+
+        assert_eq!(
+            &tokens[8..12],
+            &[
+                Token::Whitespace("\n\n"),
+                Token::Comment("-- note"),
+                Token::Whitespace("\n"),
+                Token::Keyword("log"),
+            ]
+        );
+        assert_eq!((spans[9].line, spans[9].col), (3, 1));
+
+        // The parser skips the trivia.
+        let mut p = parse::Parser::new(tokens, spans, 0);
+        assert!(p.parse_all().is_ok());
+        assert!(matches!(p.program[2], Stmt::LogStatement(_)));
+    }
+
+    #[test]
+    fn comments_are_tokens_the_parser_skips() {
+        let (tokens, _) = lex::tokenize("log 1; -- one\n--[[ two ]] log 2;").unwrap();
+        assert_eq!(tokens[3], Token::Comment("-- one"));
+        assert_eq!(tokens[4], Token::Comment("--[[ two ]]"));
+
+        let with_comments = "-- setup\nvar x = --[[ the start ]] 1;\nfor (x < 3) do { -- loop\n\tx = x + 1; -- step\n}\nlog x; -- done";
+        let without_comments = "var x = 1;\nfor (x < 3) do {\n\tx = x + 1;\n}\nlog x;";
+
+        assert_eq!(
+            json::program_to_json(&parse_to_ast(with_comments).unwrap()),
+            json::program_to_json(&parse_to_ast(without_comments).unwrap())
+        );
+    }
+
+    #[test]
+    fn scans_hex_and_exponent_numbers() {
+        for (source_code, expected) in [("1e3", 1000.0), ("2.5e-2", 0.025)] {
+            let (tokens, _) = lex::tokenize(source_code).unwrap();
+            assert_eq!(tokens, vec![Token::Number(source_code), Token::EndOfFile]);
+
+            match parse_single_expression(source_code) {
+                Ok(Expr::NumberLiteral(n)) => assert_eq!(n, expected, "{}", source_code),
+                res => panic!("Expected a number for {}, got {:?}", source_code, res),
+            }
+        }
+
+        match parse_single_expression("0xFF") {
+            Ok(Expr::IntegerLiteral(n)) => assert_eq!(n, 255),
+            res => panic!("Expected an integer for 0xFF, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn exponent_without_digits_is_an_error() {
+        match lex::tokenize("log 1e;").unwrap_err().as_slice() {
+            [lex::ScannerError::MalformedNumber { literal, position }] => {
+                assert_eq!(literal, "1e");
+                assert_eq!((position.line, position.col), (1, 5));
+            }
+            errors => panic!("Expected a malformed number, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn numbers_need_real_digits_and_one_dot() {
+        for source_code in ["1e_", "0x_", "1.2.3", "1..2"] {
+            match lex::tokenize(&format!("log {};", source_code)) {
+                Err(errors) => match errors.as_slice() {
+                    [lex::ScannerError::MalformedNumber { literal, .. }] => {
+                        assert_eq!(literal, source_code)
+                    }
+                    errors => panic!("Expected a malformed number, got {:?}", errors),
+                },
+                res => panic!("Expected an error for {}, got {:?}", source_code, res),
+            }
+        }
+
+        // A token that didn't come from the scanner is an error, not a panic.
+        let tokens = vec![Token::Number("1.2.3")];
+        let spans = vec![reef_syntax::token::Span::default()];
+        assert!(matches!(
+            parse::Parser::new(tokens, spans, 0).parse_expression(),
+            Err(parse::ParserError::SyntaxError { .. })
+        ));
+    }
+
+    #[test]
+    fn warns_about_integers_losing_precision() {
+        let warnings_for = |source_code| {
+            let (tokens, spans) = lex::tokenize(source_code).unwrap();
+            let mut p = parse::Parser::new(tokens, spans, 0);
+            p.parse_all().unwrap();
+            p.warnings
+        };
+
+        match warnings_for("log 1;\nlog 9223372036854775809;").as_slice() {
+            [parse::ParserWarning::PrecisionLoss { literal, position }] => {
+                assert_eq!(literal, "9223372036854775809");
+                assert_eq!((position.line, position.col), (2, 5));
+            }
+            warnings => panic!("Expected a precision warning, got {:?}", warnings),
+        }
+        // Integers which fit in an `i64` are stored exactly.
+        assert!(warnings_for("log 9007199254740993, 42, 1.5;").is_empty());
+    }
+
+    #[test]
+    fn underscores_in_numbers_are_ignored() {
+        for (source_code, expected) in [("1_000", 1000), ("0xFF_FF", 0xFFFF)] {
+            match parse_single_expression(source_code) {
+                Ok(Expr::IntegerLiteral(n)) => assert_eq!(n, expected, "{}", source_code),
+                res => panic!("Expected an integer for {}, got {:?}", source_code, res),
+            }
+        }
+
+        // Too big for an `i64`, so it has to be parsed as an `f64` instead.
+        let too_big = "100_000_000_000_000_000_000";
+        for (source_code, expected) in [("1_000.5", 1000.5), (too_big, 1e20)] {
+            match parse_single_expression(source_code) {
+                Ok(Expr::NumberLiteral(n)) => assert_eq!(n, expected, "{}", source_code),
+                res => panic!("Expected a number for {}, got {:?}", source_code, res),
+            }
+        }
+    }
+
+    #[test]
+    fn scanner_reports_every_unrecognised_character() {
+        let errors = lex::tokenize("var x = @1;\nlog x $ 2;").unwrap_err();
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "at line 1, column 9, unrecognised character `@`",
+                "at line 2, column 7, unrecognised character `$`",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_to_ast_reports_scanner_errors() {
+        match parse_to_ast("log 1 ! 2;") {
+            Err(parse::ParserError::SyntaxError { position, message }) => {
+                assert_eq!((position.line, position.col), (1, 7));
+                assert_eq!(message, "unrecognised character `!`");
+            }
+            res => panic!("Expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn token_display_shows_positions() {
+        let (tokens, spans) = lex::tokenize("var x = 1;\n  log x;").unwrap();
+
+        assert_eq!(
+            TokenDisplay(&tokens, &spans).to_string(),
+            concat!(
+                "  1:1  Keyword(\"var\")\n",
+                "  1:5  Identifier(\"x\")\n",
+                "  1:7  Equals\n",
+                "  1:9  Number(\"1\")\n",
+                "  1:10  Delimiter(';')\n",
+                "  2:3  Keyword(\"log\")\n",
+                "  2:7  Identifier(\"x\")\n",
+                "  2:8  Delimiter(';')\n",
+                "  2:9  EndOfFile\n",
+            )
+        );
+    }
+
+    #[test]
+    fn scanning_always_ends_with_one_end_of_file() {
+        for source_code in ["", "   \n", "-- comment", "log 1;", "log \"open", "log @;"] {
+            let mut l = lex::Scanner::new(source_code, 0);
+            let _ = l.scan();
+            let _ = l.scan();
+
+            assert_eq!(
+                l.tokens.last(),
+                Some(&Token::EndOfFile),
+                "{:?}",
+                source_code
+            );
+            assert_eq!(
+                l.tokens.iter().filter(|t| **t == Token::EndOfFile).count(),
+                1,
+                "{:?}",
+                source_code
+            );
+            assert_eq!(l.tokens.len(), l.spans.len());
+        }
+
+        let (tokens, spans) = lex::Scanner::new("log 1;\n", 0)
+            .tokens_with_trivia()
+            .unwrap();
+        assert_eq!(tokens.last(), Some(&Token::EndOfFile));
+        let end = spans.last().unwrap();
+        assert_eq!((end.line, end.col), (2, 1));
+    }
+
+    #[test]
+    fn reports_two_operators_in_a_row() {
+        match parse_source("log 1 + + 2;") {
+            Err(parse::ParserError::SyntaxError { position, message }) => {
+                assert_eq!(message, "unexpected operator `+`; expected an operand");
+                assert_eq!((position.line, position.col), (1, 9));
+            }
+            res => panic!("Expected a syntax error, got {:?}", res),
+        }
+
+        test_lexer_and_parser("log 1 + -2;");
+    }
+
+    #[test]
+    fn loop_control_outside_loop_is_a_syntax_error() {
+        test_lexer_and_parser("for (true) do { if (true) then { break; } }");
+
+        for (source_code, keyword) in [
+            ("break;", "break"),
+            ("for (true) do { fun f() { break; } }", "break"),
+            ("if (true) then { continue; }", "continue"),
+        ] {
+            match parse_source(source_code) {
+                Err(parse::ParserError::SyntaxError { message, .. }) => assert_eq!(
+                    message,
+                    format!("`{}` can only be used inside of a loop", keyword)
+                ),
+                res => panic!("Expected a syntax error, got {:?}", res),
+            }
+        }
+    }
+
+    fn parse_single_expression(source_code: &str) -> Result<Expr, parse::ParserError> {
+        let (tokens, spans) = lex::tokenize(source_code).unwrap();
+        parse::Parser::new(tokens, spans, 0).parse_expression()
+    }
+
+    /// Every expression leaves the parser on its last token, so a statement
+    /// made of a lone literal ends at the same `;` as a longer one does.
+    #[test]
+    fn booleans_can_be_whole_statements() {
+        let program = parse_to_ast("true; false; true and false; nil;\nlog 1;").unwrap();
+        let statements: Vec<String> = program
+            .iter()
+            .filter(|stmt| !matches!(stmt, Stmt::EmptyStatement))
+            .map(|stmt| stmt.to_string())
+            .collect();
+
+        assert_eq!(
+            statements,
+            vec![
+                "ExpressionStatement\n  Boolean true",
+                "ExpressionStatement\n  Boolean false",
+                "ExpressionStatement\n  ComparisonExpression And\n    Boolean true\n    Boolean false",
+                "ExpressionStatement\n  NilLiteral",
+                "LogStatement\n  IntegerLiteral 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_map_literals() {
+        let program = parse_to_ast("var m = { \"a\" = 1, \"b\" = [2] }; var e = {};").unwrap();
+
+        assert_eq!(
+            program[0].to_string(),
+            "VariableDeclaration m\n\
+             \x20 MapLiteral\n\
+             \x20   key: StringLiteral \"a\"\n\
+             \x20   value: IntegerLiteral 1\n\
+             \x20   key: StringLiteral \"b\"\n\
+             \x20   value: ArrayLiteral\n\
+             \x20     IntegerLiteral 2"
+        );
+        assert_eq!(
+            program[2].to_string(),
+            "VariableDeclaration e\n  MapLiteral"
+        );
+
+        for source_code in ["var m = { \"a\" 1 };", "var m = { \"a\" = 1 \"b\" = 2 };"] {
+            assert!(parse_to_ast(source_code).is_err(), "{}", source_code);
+        }
+        assert!(matches!(
+            parse_to_ast("var m = { \"a\" = 1,"),
+            Err(parse::ParserError::UnclosedDelimiter { delimiter: '{', .. })
+        ));
+    }
+
+    #[test]
+    fn parses_slices() {
+        let slice = |source_code: &str| parse_to_ast(source_code).unwrap()[0].to_string();
+
+        assert_eq!(
+            slice("a[1:-1];"),
+            "ExpressionStatement\n\
+             \x20 SliceExpression\n\
+             \x20   Identifier a\n\
+             \x20   start: IntegerLiteral 1\n\
+             \x20   end: UnaryExpression Minus\n\
+             \x20     IntegerLiteral 1"
+        );
+        assert_eq!(
+            slice("a[:2];"),
+            "ExpressionStatement\n  SliceExpression\n    Identifier a\n    end: IntegerLiteral 2"
+        );
+        assert_eq!(
+            slice("a[1:][0];"),
+            "ExpressionStatement\n\
+             \x20 IndexExpression\n\
+             \x20   SliceExpression\n\
+             \x20     Identifier a\n\
+             \x20     start: IntegerLiteral 1\n\
+             \x20   index: IntegerLiteral 0"
+        );
+        assert_eq!(
+            slice("a[:];"),
+            "ExpressionStatement\n  SliceExpression\n    Identifier a"
+        );
+        assert!(parse_to_ast("a[1:2:3];").is_err());
+    }
+
+    /// A `{` starting a statement is a block unless it's followed by `"key" =`.
+    #[test]
+    fn statement_braces_are_blocks_unless_a_key_follows() {
+        let program = parse_to_ast("{ \"a\"; }\n{ \"a\" = 1 }[\"a\"];").unwrap();
+
+        assert!(matches!(program[0], Stmt::BlockStatement(_)));
+        assert!(matches!(program[1], Stmt::ExpressionStatement(_)));
+    }
+
+    #[test]
+    fn constants_need_a_value() {
+        let program = parse_to_ast("const PI = 3.14;").unwrap();
+        assert_eq!(
+            program[0].to_string(),
+            "ConstantDeclaration PI\n  NumberLiteral 3.14"
+        );
+
+        match parse_source("const PI;") {
+            Err(parse::ParserError::SyntaxError { message, .. }) => {
+                assert!(message.contains("needs a value"), "{}", message)
+            }
+            res => panic!("Expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn lists_can_end_with_a_trailing_comma() {
+        let program = parse_to_ast("log 1, 2,;").unwrap();
+        assert_eq!(
+            program[0].to_string(),
+            "LogStatement\n  IntegerLiteral 1\n  IntegerLiteral 2"
+        );
+
+        for source_code in ["var a = [1, 2,];", "f(1,);"] {
+            test_lexer_and_parser(source_code);
+        }
+
+        // Only one comma can come before the end.
+        assert!(parse_source("var a = [1,,];").is_err());
+    }
+
+    #[test]
+    fn minus_is_always_a_unary_expression() {
+        for source_code in ["-5", "-x", "-(2 + 3)", "- - 5"] {
+            match parse_single_expression(source_code) {
+                Ok(Expr::UnaryExpression(UnaryOperation::Minus, _)) => {}
+                res => panic!("Expected a unary minus for {}, got {:?}", source_code, res),
+            }
+        }
+
+        // The minus applies to the left operand, not the whole expression.
+        match parse_single_expression("-5 + 3") {
+            Ok(Expr::BinaryExpression { left_side, .. }) => assert!(matches!(
+                *left_side,
+                Expr::UnaryExpression(UnaryOperation::Minus, _)
+            )),
+            res => panic!("Expected a binary expression, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn parses_a_single_expression() {
+        match parse_single_expression("1 + 2") {
+            Ok(Expr::BinaryExpression { .. }) => {}
+            res => panic!("Expected a binary expression, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn single_expression_rejects_trailing_tokens() {
+        match parse_single_expression("1 + 2;") {
+            Err(parse::ParserError::SyntaxError { position, .. }) => assert_eq!(position.col, 6),
+            res => panic!("Expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn single_expression_rejects_empty_input() {
+        assert!(parse_single_expression("").is_err());
+        assert!(parse_single_expression(";").is_err());
+    }
+
+    #[test]
+    fn token_predicates() {
+        let operands = [
+            Token::Number("1"),
+            Token::String("a"),
+            Token::Identifier("x"),
+            Token::Keyword("true"),
+            Token::Keyword("false"),
+            Token::Keyword("nil"),
+        ];
+        for token in operands {
+            assert!(token.is_operand(), "{}", token);
+            assert!(token.is_expression_start(), "{}", token);
+        }
+
+        let starts = [
+            Token::Delimiter('('),
+            Token::Delimiter('['),
+            Token::Delimiter('{'),
+            Token::BinaryOperator('-'),
+            Token::Keyword("not"),
+            Token::Keyword("typeof"),
+        ];
+        for token in starts {
+            assert!(!token.is_operand(), "{}", token);
+            assert!(token.is_expression_start(), "{}", token);
+        }
+
+        let neither = [
+            Token::Delimiter(';'),
+            Token::Delimiter(')'),
+            Token::BinaryOperator('+'),
+            Token::Keyword("var"),
+            Token::Equals,
+            Token::EndOfFile,
+        ];
+        for token in neither {
+            assert!(!token.is_operand(), "{}", token);
+            assert!(!token.is_expression_start(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn expressions_display_as_a_tree() {
+        let expr = parse_single_expression("1 + 2 * 3").unwrap();
+
+        assert_eq!(
+            expr.to_string(),
+            "BinaryExpression Plus\n\
+             \x20 IntegerLiteral 1\n\
+             \x20 BinaryExpression Multiply\n\
+             \x20   IntegerLiteral 2\n\
+             \x20   IntegerLiteral 3"
+        );
+    }
+
+    #[test]
+    fn program_displays_as_a_tree() {
+        let program = parse_to_ast("if (x < 1) then { log x, \"a\"; }").unwrap();
+
+        assert_eq!(
+            reef_syntax::tree::program_to_tree(&program),
+            "IfStatement\n\
+             \x20 condition: ComparisonExpression LessThan\n\
+             \x20   Identifier x\n\
+             \x20   IntegerLiteral 1\n\
+             \x20 body: BlockStatement\n\
+             \x20   LogStatement\n\
+             \x20     Identifier x\n\
+             \x20     StringLiteral \"a\"\n\
+             \x20   EmptyStatement\n"
+        );
+    }
+
+    #[test]
+    fn program_serialises_to_json() {
+        let mut l = lex::Scanner::new(
+            "var x = [1, \"a\tb\"];\nfun f(a) { return 2 + a[0]; }\nif (x == nil) then { log f(x); }",
+            0,
+        );
+        l.scan().unwrap();
+        let mut p = parse::Parser::new(l.tokens, l.spans, 0);
+        p.parse_all().unwrap();
+
+        let json = reef_syntax::json::program_to_json(&p.program);
+
+        assert!(json.starts_with("[{\"type\":\"VariableDeclaration\",\"name\":\"x\""));
+        for kind in [
+            "ArrayLiteral",
+            "FunctionDeclaration",
+            "ReturnStatement",
+            "BinaryExpression",
+            "IndexExpression",
+            "IfStatement",
+            "ComparisonExpression",
+            "NilLiteral",
+            "LogStatement",
+            "FunctionCall",
+        ] {
+            assert!(
+                json.contains(&format!("\"type\":\"{}\"", kind)),
+                "{} missing from {}",
+                kind,
+                json
+            );
+        }
+        assert!(json.contains("\"parameters\":[\"a\"]"));
+        assert!(json.contains("{\"type\":\"StringLiteral\",\"value\":\"a\\tb\"}"));
+        assert!(json.contains("{\"type\":\"IntegerLiteral\",\"value\":2}"));
+    }
+
+    #[test]
+    fn empty_source_is_an_empty_program() {
+        assert!(parse_to_ast("").unwrap().is_empty());
+        assert!(parse_to_ast("   \n\t").unwrap().is_empty());
+        assert!(parse_to_ast("-- just a comment").unwrap().is_empty());
+        assert!(parse_to_ast("--[[ a\nblock ]]\n-- and a line\n\n")
+            .unwrap()
+            .is_empty());
+
+        let mut p = parse::Parser::new(vec![Token::EndOfFile], vec![Default::default()], 0);
+        p.parse_all().unwrap();
+        assert!(p.program.is_empty());
+    }
 }
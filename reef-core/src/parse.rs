@@ -1,5 +1,13 @@
-use reef_syntax::{ast::*, common::*, token::Token};
-use std::{backtrace::Backtrace, mem};
+use crate::lex::ScannerError;
+use reef_syntax::{
+    ast::*,
+    common::*,
+    token::{Span, Token},
+};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    mem,
+};
 
 /// The parser is responsible for taking a vector of tokens
 /// and producing a tree-like representation of the program
@@ -8,30 +16,181 @@ use std::{backtrace::Backtrace, mem};
 pub struct Parser<'a> {
     pub program: Vec<Stmt>,
     tokens: Vec<Token<'a>>,
+    spans: Vec<Span>,
     current: usize,
     debug: u8,
+    /// Every `(` and `{` which hasn't been closed yet, along with the index
+    /// of the token that opened it.
+    open_delimiters: Vec<(char, usize)>,
+    /// How many loops the current statement is inside of, within the current
+    /// function, so that a `break` or `continue` outside of a loop can be
+    /// reported.
+    loop_depth: usize,
+    /// Problems in the program which don't stop it from being parsed.
+    pub warnings: Vec<ParserWarning>,
 }
 
 #[derive(Debug)]
 pub enum ParserError {
-    SyntaxError { position: usize, message: String },
-    UnknownToken { position: usize },
+    SyntaxError {
+        position: Span,
+        message: String,
+    },
+    UnknownToken {
+        position: Span,
+    },
+    UnclosedDelimiter {
+        delimiter: char,
+        position: Span,
+    },
+    /// A specific token was needed, but a different one was found. The tokens
+    /// are kept as they are displayed, so the error doesn't borrow the source.
+    ExpectedToken {
+        expected: String,
+        found: String,
+        position: Span,
+    },
+    /// The code ended part way through a statement, such as `var x =`. The
+    /// position is where the last token starts. `expected` is the token that
+    /// was needed next, if a specific one was.
+    UnexpectedEof {
+        expected: Option<String>,
+        position: Span,
+    },
     CurrentIndexOutOfBounds(usize),
 }
 
+impl ParserError {
+    /// Whether the code was fine as far as it went, but ended before a
+    /// statement or bracket was finished, so more code could complete it.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            Self::UnexpectedEof { .. } | Self::UnclosedDelimiter { .. }
+        )
+    }
+
+    /// Where in the source code the error happened, if it's known.
+    pub fn position(&self) -> Option<Span> {
+        match self {
+            Self::SyntaxError { position, .. }
+            | Self::UnknownToken { position }
+            | Self::UnclosedDelimiter { position, .. }
+            | Self::ExpectedToken { position, .. }
+            | Self::UnexpectedEof { position, .. } => Some(*position),
+            Self::CurrentIndexOutOfBounds(_) => None,
+        }
+    }
+}
+
+/// Scanner errors become syntax errors for anything that reports a single
+/// kind of error, such as `parse_to_ast`.
+impl From<ScannerError> for ParserError {
+    fn from(err: ScannerError) -> Self {
+        Self::SyntaxError {
+            position: err.position(),
+            message: err.message(),
+        }
+    }
+}
+
+impl Display for ParserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::SyntaxError { position, message } => {
+                write!(f, "Syntax error: at {}, {}", position, message)
+            }
+            Self::CurrentIndexOutOfBounds(position) => {
+                write!(f, "Attempt to index out of bounds. Index at {}", position)
+            }
+            Self::UnknownToken { position } => {
+                write!(f, "Encountered an unknown token at {}", position)
+            }
+            Self::UnclosedDelimiter {
+                delimiter,
+                position,
+            } => write!(
+                f,
+                "Syntax error: unclosed `{}` opened at {}",
+                delimiter, position
+            ),
+            Self::ExpectedToken {
+                expected,
+                found,
+                position,
+            } => write!(
+                f,
+                "Syntax error: expected {} but found {} at {}",
+                expected, found, position
+            ),
+            Self::UnexpectedEof {
+                expected: None,
+                position,
+            } => write!(
+                f,
+                "Syntax error: at {}, the code ended before the statement was finished",
+                position
+            ),
+            Self::UnexpectedEof {
+                expected: Some(expected),
+                position,
+            } => write!(
+                f,
+                "Syntax error: at {}, expected {} but the code ended before the statement was finished",
+                position, expected
+            ),
+        }
+    }
+}
+
+/// Something in the program which is probably a mistake, but can still be
+/// parsed and run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParserWarning {
+    /// An integer literal too big for an `i64`, which is stored as an `f64`
+    /// instead and gets rounded if it's also bigger than 2^53.
+    PrecisionLoss { literal: String, position: Span },
+}
+
+impl Display for ParserWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::PrecisionLoss { literal, position } => write!(
+                f,
+                "at {}, the integer `{}` is too big to be stored exactly and will be rounded",
+                position, literal
+            ),
+        }
+    }
+}
+
 impl<'a> Parser<'a> {
     /// Constructs a new parser, taking a vector of tokens
-    /// produced by the scanner.
-    pub fn new(tokens: Vec<Token<'a>>, debug: u8) -> Self {
+    /// produced by the scanner and where each of them starts.
+    /// Trivia tokens (whitespace and comments) are dropped.
+    pub fn new(tokens: Vec<Token<'a>>, spans: Vec<Span>, debug: u8) -> Self {
+        // The end of the tokens is the end of the program, so the scanner's
+        // `EndOfFile` token isn't needed.
+        let (tokens, spans) = tokens
+            .into_iter()
+            .zip(spans)
+            .filter(|(token, _)| !token.is_trivia() && *token != Token::EndOfFile)
+            .unzip();
+
         Self {
             tokens,
+            spans,
             debug,
             current: 0,
             program: vec![],
+            open_delimiters: vec![],
+            loop_depth: 0,
+            warnings: vec![],
         }
     }
 
-    /// Top level function for parsing every token.
+    /// Top level function for parsing every token. Parsing stops at the end
+    /// of the tokens.
     pub fn parse_all(&mut self) -> Result<(), ParserError> {
         while self.current < self.tokens.len() {
             let n = self.next_statement()?;
@@ -42,22 +201,49 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    /// Parses exactly one expression, for when a whole program isn't needed
+    /// (e.g. a calculator). Anything left over after the expression, even a
+    /// semicolon, is an error.
+    pub fn parse_expression(&mut self) -> Result<Expr, ParserError> {
+        if self.get_current_token().is_none() {
+            return Err(ParserError::SyntaxError {
+                position: self.position(),
+                message: "Expected an expression".to_string(),
+            });
+        }
+
+        let expr = self.expression()?;
+
+        // `expression` stops on the last token of the expression.
+        self.advance();
+
+        match self.get_current_token() {
+            None => Ok(expr),
+            Some(t) => Err(ParserError::SyntaxError {
+                position: self.position(),
+                message: format!("Unexpected {} after the expression", t),
+            }),
+        }
+    }
+
     fn next_statement(&mut self) -> Result<Option<Stmt>, ParserError> {
         match self.get_current_token() {
             // Statements
-            Some(Token::Keyword("var")) => Ok(Some(self.variable_declaration()?)),
-            Some(Token::Keyword("log")) => Ok(Some(self.log_statement()?)),
+            Some(Token::Keyword("var" | "const")) => Ok(Some(self.variable_declaration()?)),
+            Some(Token::Keyword("log" | "logerr")) => Ok(Some(self.log_statement()?)),
             Some(Token::Keyword("if")) => Ok(Some(self.if_statement()?)),
+            Some(Token::Keyword("for")) => Ok(Some(self.for_loop()?)),
+            Some(Token::Keyword("fun")) => Ok(Some(self.function_declaration()?)),
+            Some(Token::Keyword("struct")) => Ok(Some(self.struct_declaration()?)),
+            Some(Token::Keyword("return")) => Ok(Some(self.return_statement()?)),
+            Some(Token::Keyword(keyword @ ("break" | "continue"))) => {
+                Ok(Some(self.loop_control_statement(keyword)?))
+            }
+            Some(Token::Delimiter('{')) if self.starts_map_literal() => {
+                Ok(Some(self.expression_statement()?))
+            }
             Some(Token::Delimiter('{')) => Ok(Some(self.block_statement()?)),
 
-            // Expression statements
-            Some(Token::Keyword("true"))
-            | Some(Token::Keyword("false"))
-            | Some(Token::String(_))
-            | Some(Token::Number(_))
-            | Some(Token::BinaryOperator('-'))
-            | Some(Token::Delimiter('(')) => Ok(Some(self.expression_statement()?)),
-
             Some(Token::Identifier(_)) => {
                 let next = self.lookahead(1);
 
@@ -67,17 +253,17 @@ impl<'a> Parser<'a> {
                 }
             }
 
+            // Expression statements
+            Some(t) if t.is_expression_start() => Ok(Some(self.expression_statement()?)),
+
             Some(Token::Delimiter(';')) => {
                 self.advance();
                 Ok(Some(Stmt::EmptyStatement))
             }
 
-            _t => {
-                println!("UNKNOWN TOKEN: {:?}", _t);
-                Err(ParserError::UnknownToken {
-                    position: self.current,
-                })
-            }
+            _ => Err(ParserError::UnknownToken {
+                position: self.position(),
+            }),
         }
     }
 
@@ -86,11 +272,13 @@ impl<'a> Parser<'a> {
         let body: Stmt;
 
         self.expect(Token::Delimiter('('))?;
+        self.open_delimiter('(');
         self.advance();
 
         condition = self.expression()?;
 
         self.expect(Token::Delimiter(')'))?;
+        self.close_delimiter();
         self.expect(Token::Keyword("then"))?;
         self.advance();
 
@@ -102,12 +290,275 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses `for (condition) do { ... }`, which runs the body for as long as
+    /// the condition is true, or `for (init; condition; step) do { ... }`,
+    /// which runs `init` once before the loop and `step` after each run of the
+    /// body. Any of the three parts can be left out of the second form, and a
+    /// missing condition is always true. Like `then` after an `if`, the `do`
+    /// is required.
+    fn for_loop(&mut self) -> Result<Stmt, ParserError> {
+        self.expect(Token::Delimiter('('))?;
+        self.open_delimiter('(');
+        self.advance();
+
+        // Leaves `current` on the `;` after the init if there is one.
+        let init = match self.get_current_token() {
+            Some(Token::Keyword("var")) => Some(self.variable_declaration()?),
+            Some(Token::Identifier(_)) if self.lookahead(1) == Some(Token::Equals) => {
+                Some(self.variable_reassignment()?)
+            }
+            Some(Token::Delimiter(';')) => Some(Stmt::EmptyStatement),
+            _ => None,
+        };
+
+        let (init, condition, step) = match init {
+            Some(init) => {
+                self.advance();
+                let (condition, step) = self.for_loop_condition_and_step()?;
+                (init, condition, step)
+            }
+            None => {
+                let expr = self.expression()?;
+
+                if let Some(Token::Delimiter(';')) = self.lookahead(1) {
+                    self.advance();
+                    self.advance();
+                    let (condition, step) = self.for_loop_condition_and_step()?;
+                    (Stmt::ExpressionStatement(expr), condition, step)
+                } else {
+                    self.expect(Token::Delimiter(')'))?;
+                    (Stmt::EmptyStatement, expr, Stmt::EmptyStatement)
+                }
+            }
+        };
+
+        self.close_delimiter();
+        self.expect(Token::Keyword("do"))?;
+        self.advance();
+
+        self.loop_depth += 1;
+        let body = self.block_statement();
+        self.loop_depth -= 1;
+        let body = body?;
+
+        Ok(Stmt::ForLoop {
+            init: Box::new(init),
+            condition,
+            step: Box::new(step),
+            body: Box::new(body),
+        })
+    }
+
+    /// Parses the `condition; step)` part of a `for (init; condition; step)`
+    /// loop, starting just after the first `;` and leaving `current` on the `)`.
+    fn for_loop_condition_and_step(&mut self) -> Result<(Expr, Stmt), ParserError> {
+        let condition = match self.get_current_token() {
+            Some(Token::Delimiter(';')) => Expr::Boolean(Boolean::True),
+            _ => {
+                let condition = self.expression()?;
+                self.expect(Token::Delimiter(';'))?;
+                condition
+            }
+        };
+        self.advance();
+
+        let step = match self.get_current_token() {
+            Some(Token::Delimiter(')')) => return Ok((condition, Stmt::EmptyStatement)),
+            Some(Token::Identifier(_)) if self.lookahead(1) == Some(Token::Equals) => {
+                let (name, value) = self.assignment()?;
+                Stmt::VariableReassignment { name, value }
+            }
+            _ => Stmt::ExpressionStatement(self.expression()?),
+        };
+
+        self.expect(Token::Delimiter(')'))?;
+
+        Ok((condition, step))
+    }
+
+    /// Parses `fun name(a, b) { ... }`, a function with a list of parameter
+    /// names separated by commas and a block as its body.
+    fn function_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = match self.expect(Token::Identifier(""))? {
+            Token::Identifier(i) => String::from(i),
+            _ => {
+                return Err(ParserError::SyntaxError {
+                    position: self.position(),
+                    message: "Expected a function name after keyword `fun`".to_string(),
+                })
+            }
+        };
+
+        self.expect(Token::Delimiter('('))?;
+        self.open_delimiter('(');
+
+        let mut parameters: Vec<FunctionParameter> = vec![];
+
+        if let Some(Token::Delimiter(')')) = self.lookahead(1) {
+            self.advance();
+        } else {
+            loop {
+                let name = match self.expect(Token::Identifier(""))? {
+                    Token::Identifier(i) => String::from(i),
+                    _ => {
+                        return Err(ParserError::SyntaxError {
+                            position: self.position(),
+                            message: "Expected a parameter name".to_string(),
+                        })
+                    }
+                };
+                parameters.push(FunctionParameter { name });
+
+                // Move onto whatever follows the parameter.
+                self.advance();
+
+                match self.get_current_token() {
+                    Some(Token::Delimiter(',')) => continue,
+                    Some(Token::Delimiter(')')) => break,
+                    None => return Err(self.unclosed_delimiter_error()),
+                    Some(t) => {
+                        return Err(ParserError::SyntaxError {
+                            position: self.position(),
+                            message: format!("Expected `,` or `)` after a parameter, got {}", t),
+                        })
+                    }
+                }
+            }
+        }
+
+        self.close_delimiter();
+
+        // Skip the ')'.
+        self.advance();
+
+        // A loop around the declaration can't be broken out of from inside
+        // the function body.
+        let loop_depth = mem::take(&mut self.loop_depth);
+        let body = self.block_statement();
+        self.loop_depth = loop_depth;
+        let body = body?;
+
+        Ok(Stmt::FunctionDeclaration {
+            name,
+            parameters,
+            body: Box::new(body),
+        })
+    }
+
+    /// Parses `struct Name { field, field }`, declaring a struct type with the
+    /// given field names. A trailing comma after the last field is allowed.
+    fn struct_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = match self.expect(Token::Identifier(""))? {
+            Token::Identifier(i) => String::from(i),
+            _ => {
+                return Err(ParserError::SyntaxError {
+                    position: self.position(),
+                    message: "Expected a struct name after keyword `struct`".to_string(),
+                })
+            }
+        };
+
+        self.expect(Token::Delimiter('{'))?;
+        self.open_delimiter('{');
+
+        let mut fields: Vec<String> = vec![];
+
+        loop {
+            self.advance();
+
+            match self.get_current_token() {
+                Some(Token::Delimiter('}')) => break,
+                Some(Token::Identifier(field)) => {
+                    if fields.iter().any(|declared| declared == field) {
+                        return Err(ParserError::SyntaxError {
+                            position: self.position(),
+                            message: format!("The field `{}` is declared more than once", field),
+                        });
+                    }
+                    fields.push(String::from(field));
+                }
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected a field name, got {}", t),
+                    })
+                }
+            }
+
+            // Move onto whatever follows the field.
+            self.advance();
+
+            match self.get_current_token() {
+                Some(Token::Delimiter(',')) => continue,
+                Some(Token::Delimiter('}')) => break,
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected `,` or `}}` after a field, got {}", t),
+                    })
+                }
+            }
+        }
+
+        self.close_delimiter();
+
+        // Skip the '}'.
+        self.advance();
+
+        Ok(Stmt::StructDeclaration { name, fields })
+    }
+
+    /// Parses `break;` or `continue;`, which have to be inside of a loop.
+    fn loop_control_statement(&mut self, keyword: &str) -> Result<Stmt, ParserError> {
+        if self.loop_depth == 0 {
+            return Err(ParserError::SyntaxError {
+                position: self.position(),
+                message: format!("`{}` can only be used inside of a loop", keyword),
+            });
+        }
+
+        self.expect(Token::Delimiter(';'))?;
+
+        match keyword {
+            "break" => Ok(Stmt::Break),
+            _ => Ok(Stmt::Continue),
+        }
+    }
+
+    /// Parses `return expr;`, or a bare `return;` which returns nil.
+    fn return_statement(&mut self) -> Result<Stmt, ParserError> {
+        // Skip past the "return" keyword.
+        self.advance();
+
+        if let Some(Token::Delimiter(';')) = self.get_current_token() {
+            return Ok(Stmt::ReturnStatement(Expr::NilLiteral));
+        }
+
+        let value = self.expression()?;
+
+        self.expect(Token::Delimiter(';'))?;
+
+        Ok(Stmt::ReturnStatement(value))
+    }
+
     fn variable_reassignment(&mut self) -> Result<Stmt, ParserError> {
+        let (name, value) = self.assignment()?;
+
+        self.expect(Token::Delimiter(';'))?;
+
+        Ok(Stmt::VariableReassignment { name, value })
+    }
+
+    /// Parses `name = expression`, without a semicolon after it. Leaves
+    /// `current` on the last token of the expression.
+    fn assignment(&mut self) -> Result<(String, Expr), ParserError> {
         let name = match self.get_current_token() {
             Some(Token::Identifier(i)) => String::from(i),
             _ => {
                 return Err(ParserError::SyntaxError {
-                    position: self.current,
+                    position: self.position(),
                     message: "Help".to_string(),
                 })
             }
@@ -118,58 +569,147 @@ impl<'a> Parser<'a> {
 
         let value = self.expression()?;
 
-        self.expect(Token::Delimiter(';'))?;
-
-        Ok(Stmt::VariableReassignment {
-            name: String::from(name),
-            value: value,
-        })
+        Ok((name, value))
     }
 
-    /// The base method for parsing any kind of expression.
+    /// The base method for parsing any kind of expression. Operators are
+    /// grouped by precedence, so `1 + 2 * 3` is `1 + (2 * 3)`, and operators
+    /// with the same precedence are grouped from the left.
     fn expression(&mut self) -> Result<Expr, ParserError> {
+        self.expression_with_precedence(0)
+    }
+
+    /// Parses an operand followed by any operators which bind at least as
+    /// tightly as `min_precedence` (see `infix_precedence`), each along with
+    /// its right hand side. Leaves `current` on the last token of the
+    /// expression.
+    fn expression_with_precedence(&mut self, min_precedence: u8) -> Result<Expr, ParserError> {
+        let mut lhs = self.unary_expression()?;
+
+        while let Some(operator) = self.lookahead(1) {
+            let precedence = match infix_precedence(operator) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+
+            // Move onto the operator, and then past it.
+            self.advance();
+            self.advance();
+
+            // Two operators in a row, like `1 + + 2`. A `-` is fine, since it
+            // can make the operand negative.
+            if let Some(Token::BinaryOperator(op)) = self.get_current_token() {
+                if op != '-' {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("unexpected operator `{}`; expected an operand", op),
+                    });
+                }
+            }
+
+            // Only operators binding tighter than this one can be part of the
+            // right hand side, which is what groups equal operators leftwards.
+            let rhs = self.expression_with_precedence(precedence + 1)?;
+
+            // `1 < 2 < 3` would compare `1 < 2`, a boolean, with 3, which is
+            // never what was meant, so chained comparisons are rejected.
+            if let (Token::ComparisonOperator(_), Some(Token::ComparisonOperator(_))) =
+                (operator, self.lookahead(1))
+            {
+                self.advance();
+                return Err(ParserError::SyntaxError {
+                    position: self.position(),
+                    message: "comparisons can't be chained; add parentheses, or join them with `and` like `a < b and b < c`".to_string(),
+                });
+            }
+
+            lhs = combine_infix(lhs, operator, rhs);
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses a single operand, along with any unary operators in front of it.
+    /// Unary operators bind tighter than any infix operator, so `-a * b` is
+    /// `(-a) * b` and `not a and b` is `(not a) and b`.
+    fn unary_expression(&mut self) -> Result<Expr, ParserError> {
         match self.get_current_token() {
-            Some(Token::Keyword("true")) => Ok(Expr::Boolean(Boolean::True)),
-            Some(Token::Keyword("false")) => Ok(Expr::Boolean(Boolean::False)),
-            Some(Token::Keyword("nil")) => Ok(Expr::NilLiteral),
-            Some(Token::Delimiter('(')) => Ok(self.group_expression()?),
-            Some(Token::String(s)) => Ok(create_string_literal(s)),
+            Some(Token::Keyword(keyword @ ("not" | "typeof"))) => {
+                let operation = match keyword {
+                    "not" => UnaryOperation::Not,
+                    _ => UnaryOperation::TypeOf,
+                };
+
+                // Skip past the keyword.
+                self.advance();
+
+                Ok(Expr::UnaryExpression(
+                    operation,
+                    Box::new(self.unary_expression()?),
+                ))
+            }
             Some(Token::BinaryOperator('-')) => {
-                // Skip past the '-'. May cause issues down the line but idc.
+                // Skip past the '-'.
                 self.advance();
 
                 match self.get_current_token() {
-                    Some(Token::Number(_))
-                    | Some(Token::Identifier(_))
-                    | Some(Token::Delimiter('(')) => Ok(Expr::UnaryExpression(
+                    Some(t) if t.is_expression_start() => Ok(Expr::UnaryExpression(
                         UnaryOperation::Minus,
-                        Box::new(self.expression()?),
+                        Box::new(self.unary_expression()?),
                     )),
+                    None => Err(ParserError::UnexpectedEof {
+                        expected: None,
+                        position: self.position(),
+                    }),
                     _ => Err(ParserError::SyntaxError {
-                        position: self.current,
+                        position: self.position(),
                         message: format!("Wrong kind of argument after a unary operater bro!"),
                     }),
                 }
             }
-            Some(Token::Number(n)) => {
-                let next = self.lookahead(1);
+            _ => self.primary_expression(),
+        }
+    }
 
-                match next {
-                    Some(Token::BinaryOperator(_)) => Ok(self.binary_expression()?),
-                    Some(Token::ComparisonOperator(_)) => Ok(self.comparison_expression()?),
-                    _ => Ok(create_number_literal(n)),
-                }
+    /// Parses an operand with no operators: a literal, an identifier, a call,
+    /// a group, an array or a struct, along with any indexing or field access
+    /// after it. Like every expression, it leaves `current` on its last token,
+    /// so a literal doesn't move `current` at all.
+    fn primary_expression(&mut self) -> Result<Expr, ParserError> {
+        match self.get_current_token() {
+            Some(Token::Keyword(b @ ("true" | "false"))) => Ok(create_boolean_literal(b)),
+            Some(Token::Keyword("nil")) => Ok(Expr::NilLiteral),
+            Some(Token::Delimiter('(')) => {
+                let group = self.group_expression()?;
+                self.postfix_expression(group)
+            }
+            Some(Token::Delimiter('[')) => {
+                let array = self.array_literal()?;
+                self.postfix_expression(array)
             }
+            Some(Token::Delimiter('{')) => {
+                let map = self.map_literal()?;
+                self.postfix_expression(map)
+            }
+            Some(Token::String(s)) => self.postfix_expression(create_string_literal(s)),
+            Some(Token::Number(n)) => self.number_literal(n),
             Some(Token::Identifier(ident)) => {
-                // TODO: abstract this to a different function
-                let next = self.lookahead(1);
+                let expr = match self.lookahead(1) {
+                    Some(Token::Delimiter('(')) => self.function_call()?,
+                    Some(Token::Delimiter('{')) => self.struct_init(ident)?,
+                    _ => Expr::Identifier(String::from(ident)),
+                };
 
-                match next {
-                    Some(Token::BinaryOperator(_)) => Ok(self.binary_expression()?),
-                    _ => Ok(Expr::Identifier(String::from(ident))),
-                }
+                self.postfix_expression(expr)
             }
-            _token => panic!("[!] {:?}", _token),
+            None => Err(ParserError::UnexpectedEof {
+                expected: None,
+                position: self.position(),
+            }),
+            Some(token) => Err(ParserError::SyntaxError {
+                position: self.position(),
+                message: format!("Expected an expression, got {}", token),
+            }),
         }
     }
 
@@ -177,8 +717,10 @@ impl<'a> Parser<'a> {
         // log expr1, expr2, expr3;
         // log expr1;
         // log;
+        // logerr expr1, expr2;
 
-        // Skip past the "log" keyword.
+        let to_stderr = self.get_current_token() == Some(Token::Keyword("logerr"));
+        // Skip past the "log" or "logerr" keyword.
         self.advance();
 
         let expressions = self.parse_call_site_arguments()?;
@@ -186,31 +728,36 @@ impl<'a> Parser<'a> {
 
         self.expect(Token::Delimiter(';'))?;
 
-        Ok(Stmt::LogStatement(expressions))
+        if to_stderr {
+            Ok(Stmt::LogErrStatement(expressions))
+        } else {
+            Ok(Stmt::LogStatement(expressions))
+        }
     }
 
     fn block_statement(&mut self) -> Result<Stmt, ParserError> {
+        self.open_delimiter('{');
+
         // Skip the '{'.
         self.advance();
-        println!("{:?}", self.get_current_token());
 
         let mut statements: Vec<Stmt> = vec![];
 
         while self.current < self.tokens.len() && self.get_current_token() != None {
-            let s = self.next_statement()?;
-            statements.push(s.unwrap());
-
             match self.get_current_token() {
                 Some(Token::Delimiter('}')) => {
+                    self.close_delimiter();
                     self.advance();
-                    break;
+                    return Ok(Stmt::BlockStatement(statements));
+                }
+                _ => {
+                    let s = self.next_statement()?;
+                    statements.push(s.unwrap());
                 }
-                None => panic!("Expected '}}' to close a compound statement."),
-                _ => continue,
             }
         }
 
-        Ok(Stmt::BlockStatement(statements))
+        Err(self.unclosed_delimiter_error())
     }
 
     /// Collects a list of arguments (expressions) separated by commas.
@@ -221,14 +768,15 @@ impl<'a> Parser<'a> {
         // so im just going to leave it and pray it keeps working!
         while let Some(token) = self.get_current_token() {
             let expr = match token {
-                Token::String(_)
-                | Token::Number(_)
-                | Token::Identifier(_)
-                | Token::Delimiter('(')
-                | Token::BinaryOperator('-')
-                | Token::Keyword("true")
-                | Token::Keyword("false") => self.expression()?,
-
+                t if t.is_expression_start() => self.expression()?,
+                // Only a comma leads back around, so something like `[1,,]`
+                // is missing an expression.
+                t if !collected.is_empty() => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected an expression after `,`, got {}", t),
+                    })
+                }
                 _ => break,
             };
             collected.push(expr);
@@ -236,6 +784,12 @@ impl<'a> Parser<'a> {
             let next = self.lookahead(1);
 
             match next {
+                // A trailing comma, like `log 1, 2,;`, ends the arguments on
+                // the comma, so the caller finds the terminator next.
+                Some(Token::Delimiter(',')) if is_list_terminator(self.lookahead(2)) => {
+                    self.advance();
+                    break;
+                }
                 Some(Token::Delimiter(',')) => {
                     // Really janky but the first advance skips the expression,
                     // the second one skips the comma. Im a lil stupid so just
@@ -253,185 +807,331 @@ impl<'a> Parser<'a> {
         Ok(collected)
     }
 
-    /// Generates an expression statement. An expression statement is simply an expression
-    /// but as a statement. For example, `10 + 5;` is an expression statement.
-    fn expression_statement(&mut self) -> Result<Stmt, ParserError> {
-        let expr = self.expression()?;
-        self.expect(Token::Delimiter(';'))?;
+    /// Generates a function call, which is a function name followed by a list of
+    /// arguments inside of brackets, e.g. `add(1, 2)`.
+    fn function_call(&mut self) -> Result<Expr, ParserError> {
+        let func_name = match self.get_current_token() {
+            Some(Token::Identifier(i)) => String::from(i),
+            _ => {
+                return Err(ParserError::SyntaxError {
+                    position: self.position(),
+                    message: "Expected a function name".to_string(),
+                })
+            }
+        };
 
-        Ok(Stmt::ExpressionStatement(expr))
-    }
+        self.expect(Token::Delimiter('('))?;
+        self.open_delimiter('(');
 
-    /// Generates a group expression, which is any expression inside of brackets.
-    fn group_expression(&mut self) -> Result<Expr, ParserError> {
-        // Starts with a '(', should also end with a ')'.
+        let arguments = match self.lookahead(1) {
+            Some(Token::Delimiter(')')) => vec![],
+            _ => {
+                // Skip the opening bracket
+                self.advance();
+                self.parse_call_site_arguments()?
+            }
+        };
 
-        // Skip the opening bracket
-        self.advance();
+        self.expect(Token::Delimiter(')'))?;
+        self.close_delimiter();
+
+        Ok(Expr::FunctionCall {
+            func_name,
+            arguments: arguments
+                .into_iter()
+                .map(|value| FunctionArgument { value })
+                .collect(),
+        })
+    }
 
-        let inner = self.expression()?;
+    /// Generates an array literal, which is a list of expressions separated by
+    /// commas inside of square brackets, e.g. `[1, 2, 3]`.
+    fn array_literal(&mut self) -> Result<Expr, ParserError> {
+        self.open_delimiter('[');
 
-        self.expect(Token::Delimiter(')'))?;
+        let elements = match self.lookahead(1) {
+            Some(Token::Delimiter(']')) => vec![],
+            _ => {
+                // Skip the opening bracket
+                self.advance();
+                self.parse_call_site_arguments()?
+            }
+        };
 
-        Ok(Expr::GroupExpression(Box::new(inner)))
+        self.expect(Token::Delimiter(']'))?;
+        self.close_delimiter();
+
+        Ok(Expr::ArrayLiteral(elements))
     }
 
-    /// Generates a binary expression, returning Ok if it was successful.
-    fn binary_expression(&mut self) -> Result<Expr, ParserError> {
-        let lhs: Expr;
-        let rhs: Expr;
-        let operator: BinaryExprOperator;
-
-        // The left hand side of the binary expression. Creates a number from a Number token,
-        // a string from a String token, and keeps track of identifiers. If the current token
-        // isn't a valid type, it simply is turned into Nil.
-        lhs = match self.get_current_token() {
-            Some(Token::Keyword("true")) => Expr::Boolean(Boolean::True),
-            Some(Token::Keyword("false")) => Expr::Boolean(Boolean::False),
-            Some(Token::Delimiter('(')) => self.group_expression()?,
-            Some(Token::String(s)) => create_string_literal(s),
-            Some(Token::BinaryOperator('-')) => {
-                // Skip past the '-'. May cause issues down the line but idc.
+    /// Wraps `target` in an index expression for every `[index]` that follows
+    /// it, and a field access for every `.field`, so `a[0].x` is the `x` field
+    /// of `a[0]`. Returns `target` as it is if nothing follows it.
+    fn postfix_expression(&mut self, target: Expr) -> Result<Expr, ParserError> {
+        let mut target = target;
+
+        while let Some(Token::Delimiter(delimiter @ ('[' | '.'))) = self.lookahead(1) {
+            // Move onto the '[' or '.'.
+            self.advance();
+
+            if delimiter == '.' {
                 self.advance();
-                match self.get_current_token() {
-                    Some(Token::Number(n)) => create_number_literal(&*format!("-{}", n)),
-                    _ => {
+
+                let field = match self.get_current_token() {
+                    Some(Token::Identifier(field)) => String::from(field),
+                    None => {
+                        return Err(ParserError::UnexpectedEof {
+                            expected: None,
+                            position: self.position(),
+                        })
+                    }
+                    Some(t) => {
                         return Err(ParserError::SyntaxError {
-                            position: self.current,
-                            message: String::new(),
+                            position: self.position(),
+                            message: format!("Expected a field name after `.`, got {}", t),
                         })
                     }
-                }
-            }
-            Some(Token::Number(n)) => {
-                let next = self.lookahead(1);
+                };
 
-                match next {
-                    _ => create_number_literal(n),
-                }
+                target = Expr::FieldAccess {
+                    target: Box::new(target),
+                    field,
+                };
+                continue;
             }
-            Some(Token::Identifier(ident)) => {
-                // TODO: abstract this to a different function
-                let next = self.lookahead(1);
 
-                match next {
-                    _ => Expr::Identifier(String::from(ident)),
+            self.open_delimiter('[');
+            self.advance();
+
+            // `[:end]` leaves out the start of a slice.
+            let index = match self.get_current_token() {
+                Some(Token::Delimiter(':')) => None,
+                _ => Some(self.expression()?),
+            };
+
+            let is_slice = match index {
+                None => true,
+                Some(_) if self.lookahead(1) == Some(Token::Delimiter(':')) => {
+                    self.advance();
+                    true
                 }
+                Some(_) => false,
+            };
+
+            if !is_slice {
+                self.expect(Token::Delimiter(']'))?;
+                self.close_delimiter();
+
+                target = Expr::IndexExpression {
+                    target: Box::new(target),
+                    index: Box::new(index.expect("an index is parsed unless it's a slice")),
+                };
+                continue;
             }
-            _ => Expr::NilLiteral,
-        };
 
-        // Creates a BinaryExprOperator containing the operator used in the binary expression.
-        // Panics if the token isn't a binary operator.
-        operator = match self.expect(Token::BinaryOperator(' '))? {
-            Token::BinaryOperator(op) => match op {
-                '+' => BinaryExprOperator::Plus,
-                '-' => BinaryExprOperator::Minus,
-                '*' => BinaryExprOperator::Multiply,
-                '/' => BinaryExprOperator::Divide,
-                '%' => BinaryExprOperator::Modulus,
+            // Now on the `:`. `[start:]` leaves out the end.
+            let end = match self.lookahead(1) {
+                Some(Token::Delimiter(']')) => None,
                 _ => {
-                    return Err(ParserError::UnknownToken {
-                        position: self.current,
+                    self.advance();
+                    Some(Box::new(self.expression()?))
+                }
+            };
+
+            self.expect(Token::Delimiter(']'))?;
+            self.close_delimiter();
+
+            target = Expr::SliceExpression {
+                target: Box::new(target),
+                start: index.map(Box::new),
+                end,
+            };
+        }
+
+        Ok(target)
+    }
+
+    /// Parses `Name { field = value, ... }`, starting on the name. Fields can
+    /// be given in any order, but only once each. Leaves `current` on the `}`.
+    fn struct_init(&mut self, name: &str) -> Result<Expr, ParserError> {
+        // Move onto the '{'.
+        self.advance();
+        self.open_delimiter('{');
+
+        let mut fields: Vec<(String, Expr)> = vec![];
+
+        loop {
+            self.advance();
+
+            let field = match self.get_current_token() {
+                Some(Token::Delimiter('}')) => break,
+                Some(Token::Identifier(field)) => String::from(field),
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!(
+                            "Expected a field name in `{} {{ ... }}`, got {}",
+                            name, t
+                        ),
                     })
                 }
-            },
-            _t => {
+            };
+
+            if fields.iter().any(|(given, _)| *given == field) {
                 return Err(ParserError::SyntaxError {
-                    position: self.current,
-                    message: String::new(),
-                })
+                    position: self.position(),
+                    message: format!("The field `{}` is given more than once", field),
+                });
             }
-        };
 
-        // Pass the operator.
-        self.advance();
+            self.expect(Token::Equals)?;
+            self.advance();
+            fields.push((field, self.expression()?));
 
-        // The right hand side of the expression. Could be any expression, so the base expression
-        // method is used.
-        rhs = self.expression()?;
+            // Move onto whatever follows the value.
+            self.advance();
 
-        Ok(Expr::BinaryExpression {
-            left_side: Box::new(lhs),
-            right_side: Box::new(rhs),
-            operator,
+            match self.get_current_token() {
+                Some(Token::Delimiter(',')) => continue,
+                Some(Token::Delimiter('}')) => break,
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected `,` or `}}` after a field, got {}", t),
+                    })
+                }
+            }
+        }
+
+        self.close_delimiter();
+
+        Ok(Expr::StructInit {
+            name: String::from(name),
+            fields,
         })
     }
 
-    fn comparison_expression(&mut self) -> Result<Expr, ParserError> {
-        let lhs: Expr;
-        let rhs: Expr;
-        let operator: ComparisonOperator;
-
-        lhs = match self.get_current_token() {
-            Some(Token::Keyword("true")) => Expr::Boolean(Boolean::True),
-            Some(Token::Keyword("false")) => Expr::Boolean(Boolean::False),
-            Some(Token::Delimiter('(')) => self.group_expression()?,
-            Some(Token::String(s)) => create_string_literal(s),
-            Some(Token::BinaryOperator('-')) => {
-                // Skip past the '-'. May cause issues down the line but idc.
-                self.advance();
-                match self.get_current_token() {
-                    Some(Token::Number(n)) => create_number_literal(&*format!("-{}", n)),
-                    _ => {
-                        return Err(ParserError::SyntaxError {
-                            position: self.current,
-                            message: String::new(),
-                        })
-                    }
+    /// Generates a map literal, like `{ "a" = 1, "b" = 2 }`. Leaves `current`
+    /// on the closing `}`.
+    ///
+    /// Where an expression is expected, a `{` always starts a map. At the
+    /// start of a statement it starts a block instead, unless it is followed
+    /// by a string and then `=` (see `starts_map_literal`), which a block
+    /// never could be.
+    fn map_literal(&mut self) -> Result<Expr, ParserError> {
+        self.open_delimiter('{');
+
+        let mut entries: Vec<(Expr, Expr)> = vec![];
+
+        loop {
+            self.advance();
+
+            let key = match self.get_current_token() {
+                Some(Token::Delimiter('}')) => break,
+                Some(t) if t.is_expression_start() => self.expression()?,
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected a key in a map, got {}", t),
+                    })
                 }
-            }
-            Some(Token::Number(n)) => {
-                let next = self.lookahead(1);
+            };
 
-                match next {
-                    _ => create_number_literal(n),
-                }
-            }
-            Some(Token::Identifier(ident)) => {
-                // TODO: abstract this to a different function
-                let next = self.lookahead(1);
+            self.expect(Token::Equals)?;
+            self.advance();
+            entries.push((key, self.expression()?));
 
-                match next {
-                    _ => Expr::Identifier(String::from(ident)),
+            // Move onto whatever follows the value.
+            self.advance();
+
+            match self.get_current_token() {
+                Some(Token::Delimiter(',')) => continue,
+                Some(Token::Delimiter('}')) => break,
+                None => return Err(self.unclosed_delimiter_error()),
+                Some(t) => {
+                    return Err(ParserError::SyntaxError {
+                        position: self.position(),
+                        message: format!("Expected `,` or `}}` after a map entry, got {}", t),
+                    })
                 }
             }
-            _ => Expr::NilLiteral,
-        };
+        }
 
-        operator = match self.expect(Token::ComparisonOperator(ComparisonOperator::Or))? {
-            Token::ComparisonOperator(op) => op,
-            _t => {
-                return Err(ParserError::SyntaxError {
-                    position: self.current,
-                    message: String::new(),
-                })
-            }
-        };
+        self.close_delimiter();
+
+        Ok(Expr::MapLiteral(entries))
+    }
+
+    /// Whether the `{` at the start of a statement starts a map literal rather
+    /// than a block, because it is followed by `"key" =`.
+    fn starts_map_literal(&self) -> bool {
+        matches!(
+            (self.lookahead(1), self.lookahead(2)),
+            (Some(Token::String(_)), Some(Token::Equals))
+        )
+    }
+
+    /// Generates an expression statement. An expression statement is simply an expression
+    /// but as a statement. For example, `10 + 5;` is an expression statement.
+    fn expression_statement(&mut self) -> Result<Stmt, ParserError> {
+        let expr = self.expression()?;
+        self.expect(Token::Delimiter(';'))?;
+
+        Ok(Stmt::ExpressionStatement(expr))
+    }
 
+    /// Generates a group expression, which is any expression inside of brackets.
+    fn group_expression(&mut self) -> Result<Expr, ParserError> {
+        // Starts with a '(', should also end with a ')'.
+
+        self.open_delimiter('(');
+
+        // Skip the opening bracket
         self.advance();
 
-        rhs = self.expression()?;
+        let inner = self.expression()?;
 
-        Ok(Expr::ComparisonExpression {
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
-            operator,
-        })
+        self.expect(Token::Delimiter(')'))?;
+        self.close_delimiter();
+
+        Ok(Expr::GroupExpression(Box::new(inner)))
     }
 
+    /// Generates a binary expression, returning Ok if it was successful.
     /// Creates a variable declaration with a name (identifier) and a value (expression).
     fn variable_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = match self.get_current_token() {
+            Some(Token::Keyword(keyword)) => keyword,
+            _ => "var",
+        };
+
         let name = match self.expect(Token::Identifier(""))? {
             Token::Identifier(i) => String::from(i),
             _ => {
                 return Err(ParserError::SyntaxError {
-                    position: self.current,
-                    message: "Expected an identifier after keyword `var`".to_string(),
+                    position: self.position(),
+                    message: format!("Expected an identifier after keyword `{}`", keyword),
                 })
             }
         };
 
+        // `var x;` declares `x` without a value, but a constant could never
+        // be given one later.
+        if let Some(Token::Delimiter(';')) = self.lookahead(1) {
+            self.advance();
+
+            if keyword == "const" {
+                return Err(ParserError::SyntaxError {
+                    position: self.position(),
+                    message: format!("A constant needs a value, like `const {} = 1;`", name),
+                });
+            }
+            return Ok(Stmt::VariableDeclaration { name, value: None });
+        }
+
         self.expect(Token::BinaryOperator('='))?;
 
         // Skip '='
@@ -441,7 +1141,13 @@ impl<'a> Parser<'a> {
 
         self.expect(Token::Delimiter(';'))?;
 
-        Ok(Stmt::VariableDeclaration { name, value })
+        if keyword == "const" {
+            return Ok(Stmt::ConstantDeclaration { name, value });
+        }
+        Ok(Stmt::VariableDeclaration {
+            name,
+            value: Some(value),
+        })
     }
 
     /// Pushes `node` to `self.program`.
@@ -453,8 +1159,66 @@ impl<'a> Parser<'a> {
         self.program.push(node);
     }
 
+    /// Remembers that `delimiter` was opened at the current token, so that if
+    /// it is never closed the error can point back at it.
+    fn open_delimiter(&mut self, delimiter: char) {
+        self.open_delimiters.push((delimiter, self.current));
+    }
+
+    /// Forgets about the innermost open delimiter once it has been closed.
+    fn close_delimiter(&mut self) {
+        self.open_delimiters.pop();
+    }
+
+    /// Builds an error pointing at the line of the innermost delimiter that
+    /// was never closed.
+    fn unclosed_delimiter_error(&self) -> ParserError {
+        match self.open_delimiters.last() {
+            Some(&(delimiter, index)) => ParserError::UnclosedDelimiter {
+                delimiter,
+                position: self.spans.get(index).copied().unwrap_or_default(),
+            },
+            None => ParserError::CurrentIndexOutOfBounds(self.current),
+        }
+    }
+
+    /// The span of every token being parsed, stored at the same index as the
+    /// token it belongs to.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Returns where the current token starts in the source code. Past the
+    /// end of the tokens, this is where the last token starts.
+    fn position(&self) -> Span {
+        match self.spans.get(self.current) {
+            Some(span) => *span,
+            None => self.spans.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Creates a number literal from the current token's text `n`, warning
+    /// if it is an integer too big to be stored exactly. The scanner already
+    /// rejects malformed numbers, but tokens built by hand could still have
+    /// one.
+    fn number_literal(&mut self, n: &str) -> Result<Expr, ParserError> {
+        let literal = create_number_literal(n).ok_or_else(|| ParserError::SyntaxError {
+            position: self.position(),
+            message: format!("malformed number `{}`", n),
+        })?;
+
+        if loses_precision(n) {
+            self.warnings.push(ParserWarning::PrecisionLoss {
+                literal: n.to_string(),
+                position: self.position(),
+            });
+        }
+
+        Ok(literal)
+    }
+
     /// Gets the token at `current + distance`.
-    fn lookahead(&self, distance: usize) -> Option<Token<'_>> {
+    fn lookahead(&self, distance: usize) -> Option<Token<'a>> {
         if self.current + distance >= self.tokens.len() {
             if self.debug >= 1 {
                 println!(
@@ -478,7 +1242,7 @@ impl<'a> Parser<'a> {
     }
 
     /// Returns the token at index `current`.
-    fn get_current_token(&self) -> Option<Token<'_>> {
+    fn get_current_token(&self) -> Option<Token<'a>> {
         if self.current >= self.tokens.len() {
             return None;
         }
@@ -506,67 +1270,169 @@ impl<'a> Parser<'a> {
 
         let token = self.get_current_token();
 
-        // At the end of the file.
-        if token.is_none() {
-            use ParserError::*;
-            use Token::*;
-
-            match expected {
-                Delimiter(';') => SyntaxError {
-                    position: self.current,
-                    message: format!("Expected semicolon"),
-                },
-                Number(_) => SyntaxError {
-                    position: self.current,
-                    message: format!("Expected Number"),
-                },
-                String(_) => SyntaxError {
-                    position: self.current,
-                    message: format!("Expected String"),
-                },
-                BinaryOperator(op) => SyntaxError {
-                    position: self.current,
-                    message: format!("Expected {}", op),
-                },
-                _ => CurrentIndexOutOfBounds(self.current),
-            };
+        // At the end of the file with a bracket still open, the missing closing
+        // bracket is a far more useful thing to report.
+        if token.is_none() && !self.open_delimiters.is_empty() {
+            return Err(self.unclosed_delimiter_error());
         }
 
         // Using mem::discriminant takes the variant of the enum at face value,
-        // ignoring the value stored inside.
-        if token.is_some() && token.unwrap() == expected
-            || mem::discriminant(&expected) == mem::discriminant(&Token::Identifier(""))
+        // ignoring the value stored inside, so any of these will do.
+        let any_of_kind = mem::discriminant(&expected) == mem::discriminant(&Token::Identifier(""))
             || mem::discriminant(&expected) == mem::discriminant(&Token::BinaryOperator(' '))
             || mem::discriminant(&expected)
-                == mem::discriminant(&Token::ComparisonOperator(ComparisonOperator::Or))
-        {
+                == mem::discriminant(&Token::ComparisonOperator(ComparisonOperator::Or));
+
+        // At the end of the file. Only a specific token is worth naming.
+        if token.is_none() {
+            return Err(ParserError::UnexpectedEof {
+                expected: (!any_of_kind).then(|| expected.to_string()),
+                position: self.position(),
+            });
+        }
+
+        if token.is_some() && token.unwrap() == expected || any_of_kind {
             // println!("[?] {:?} == {:?}", token, expected);
             Ok(token.unwrap())
         } else {
             // println!("[?] {:?} != {:?}", token, expected);
-            println!("{:?}", &self.tokens[0..self.current]);
-            Err(ParserError::SyntaxError {
-                position: self.current,
-                message: format!(
-                    "Expected {}, got {}. Backtrace: {}",
-                    expected,
-                    token.unwrap_or(Token::EndOfFile),
-                    Backtrace::capture()
-                ),
+            Err(ParserError::ExpectedToken {
+                expected: expected.to_string(),
+                found: token.unwrap_or(Token::EndOfFile).to_string(),
+                position: self.position(),
             })
         }
     }
 }
 
-/// Attempts to convert n into a number and returns a wrapper around n.
-fn create_number_literal(n: &str) -> Expr {
-    let p = n.parse::<f64>();
+/// Whether `token` ends a list of arguments or array elements.
+fn is_list_terminator(token: Option<Token>) -> bool {
+    matches!(token, Some(Token::Delimiter(';' | ')' | ']')))
+}
+
+/// How tightly an infix operator binds its operands, or `None` if `token`
+/// isn't an infix operator. Higher numbers bind tighter. The bitwise operators
+/// bind tighter than comparisons like in Rust, so `6 & 3 == 2` is
+/// `(6 & 3) == 2`.
+fn infix_precedence(token: Token) -> Option<u8> {
+    match token {
+        Token::Keyword("or") => Some(1),
+        Token::Keyword("and") => Some(2),
+        Token::ComparisonOperator(_) => Some(3),
+        Token::BitwiseOperator("|") => Some(4),
+        Token::BitwiseOperator("^") => Some(5),
+        Token::BitwiseOperator("&") => Some(6),
+        Token::BitwiseOperator(_) => Some(7),
+        Token::BinaryOperator('+' | '-') => Some(8),
+        Token::BinaryOperator(_) => Some(9),
+        _ => None,
+    }
+}
+
+/// Builds the expression for `lhs <operator> rhs`. `operator` has to be a
+/// token `infix_precedence` accepts.
+fn combine_infix(lhs: Expr, operator: Token, rhs: Expr) -> Expr {
+    let operator = match operator {
+        Token::Keyword("or") => ComparisonOperator::Or,
+        Token::Keyword("and") => ComparisonOperator::And,
+        Token::ComparisonOperator(operator) => operator,
+        Token::BinaryOperator(op) => {
+            return Expr::BinaryExpression {
+                left_side: Box::new(lhs),
+                right_side: Box::new(rhs),
+                operator: match op {
+                    '+' => BinaryExprOperator::Plus,
+                    '-' => BinaryExprOperator::Minus,
+                    '*' => BinaryExprOperator::Multiply,
+                    '/' => BinaryExprOperator::Divide,
+                    _ => BinaryExprOperator::Modulus,
+                },
+            }
+        }
+        Token::BitwiseOperator(op) => {
+            return Expr::BinaryExpression {
+                left_side: Box::new(lhs),
+                right_side: Box::new(rhs),
+                operator: match op {
+                    "&" => BinaryExprOperator::BitwiseAnd,
+                    "|" => BinaryExprOperator::BitwiseOr,
+                    "^" => BinaryExprOperator::BitwiseXor,
+                    "<<" => BinaryExprOperator::ShiftLeft,
+                    _ => BinaryExprOperator::ShiftRight,
+                },
+            }
+        }
+        _ => unreachable!("{} isn't an infix operator", operator),
+    };
+
+    Expr::ComparisonExpression {
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        operator,
+    }
+}
+
+/// Whether `n` is an integer literal too big to be stored exactly, meaning it
+/// doesn't fit in an `i64` and has to be stored as an `f64` instead.
+fn loses_precision(n: &str) -> bool {
+    let unsigned = n.replace('_', "");
+
+    let integer = match unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        Some(digits) => u128::from_str_radix(digits, 16),
+        // Anything with a `.` or an exponent isn't an integer literal.
+        None if unsigned.contains(['.', 'e', 'E']) => return false,
+        None => unsigned.parse::<u128>(),
+    };
+
+    match integer {
+        Ok(i) => i > i64::MAX as u128,
+        // Too big even for a u128.
+        Err(_) => true,
+    }
+}
+
+/// Attempts to convert n into a number and returns a wrapper around n. Whole
+/// numbers written without a `.` or an exponent are integers, as long as they
+/// fit in an `i64`, and everything else is an `f64`. Underscores between
+/// digits are only there to make the number easier to read. Returns `None` if
+/// n isn't a valid number.
+fn create_number_literal(n: &str) -> Option<Expr> {
+    let n = &n.replace('_', "");
 
-    match p {
-        Ok(v) => Expr::NumberLiteral(v),
-        Err(e) => {
-            panic!("Error unwrapping {}: {:?}", n, e);
+    if let Some(digits) = n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) {
+        if digits.is_empty() {
+            return None;
+        }
+        if let Ok(value) = i64::from_str_radix(digits, 16) {
+            return Some(Expr::IntegerLiteral(value));
         }
+
+        // `f64` can't parse hex, so the digits are added up by hand.
+        let value = digits
+            .chars()
+            .map(|c| c.to_digit(16))
+            .try_fold(0.0, |total, digit| Some(total * 16.0 + digit? as f64))?;
+
+        return Some(Expr::NumberLiteral(value));
+    }
+
+    if !n.contains(['.', 'e', 'E']) {
+        if let Ok(value) = n.parse::<i64>() {
+            return Some(Expr::IntegerLiteral(value));
+        }
+    }
+
+    n.parse::<f64>().ok().map(Expr::NumberLiteral)
+}
+
+/// Creates a boolean literal from the text of a `true` or `false` keyword.
+fn create_boolean_literal(b: &str) -> Expr {
+    match b {
+        "true" => Expr::Boolean(Boolean::True),
+        _ => Expr::Boolean(Boolean::False),
     }
 }
 
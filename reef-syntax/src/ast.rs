@@ -2,7 +2,9 @@ use crate::common::*;
 
 #[derive(Debug, Clone)]
 pub enum UnaryOperation {
-    Minus,
+    Minus,  // -expr
+    Not,    // not expr
+    TypeOf, // typeof expr
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,6 +14,12 @@ pub enum BinaryExprOperator {
     Multiply,
     Divide,
     Modulus,
+    // The bitwise operators only work on whole numbers.
+    BitwiseAnd, // &
+    BitwiseOr,  // |
+    BitwiseXor, // ^
+    ShiftLeft,  // <<
+    ShiftRight, // >>
 }
 
 #[derive(Debug, Clone)]
@@ -20,33 +28,66 @@ pub enum Stmt {
     BlockStatement(Vec<Stmt>), // { ...stmt }
     ExpressionStatement(Expr), // any expr;
     LogStatement(Vec<Expr>),   // log ...expr;
-    ReturnStatement(Expr),     // return expr;
+    /// Like `log`, but written to stderr. `logerr ...expr;`
+    LogErrStatement(Vec<Expr>),
+    ReturnStatement(Expr), // return expr;
+    /// Stops the innermost loop. Only allowed inside a loop body.
+    Break, // break;
+    /// Skips the rest of the innermost loop's body, going back to checking its
+    /// condition. Only allowed inside a loop body.
+    Continue, // continue;
     IfStatement {
         condition: Expr,
         body: Box<Stmt>,
     }, // if (condition) then { ...stmt }
+    /// `init` runs once before the loop, in a scope of its own which lasts
+    /// for the whole loop, and `step` runs after every run of the body. A
+    /// loop with only a condition has empty statements for both.
     ForLoop {
+        init: Box<Stmt>,
         condition: Expr,
+        step: Box<Stmt>,
         body: Box<Stmt>,
-    }, // for (condition) do { ...stmt }
+    }, // for (init; condition; step) do { ...stmt } or for (condition) do { ...stmt }
+    /// Creates a new variable in the current scope. Declaring a name that the
+    /// scope already has is an error. Without a value, the variable is nil.
     VariableDeclaration {
         name: String,
-        value: Expr, // might change this to Option<Expr> to allow for uninitialised vars
-    }, // var var_name = expr;
+        value: Option<Expr>,
+    }, // var var_name = expr; or var var_name;
+    /// Like a variable declaration, but the variable can never be reassigned,
+    /// so it always needs a value.
+    ConstantDeclaration {
+        name: String,
+        value: Expr,
+    }, // const NAME = expr;
+    /// Changes the value of a variable that has already been declared, either
+    /// in the current scope or one of its parents. Never creates a variable, so
+    /// reassigning a name that was never declared is an error.
     VariableReassignment {
         name: String,
         value: Expr,
-    },
+    }, // var_name = expr;
     FunctionDeclaration {
         name: String,
         parameters: Vec<FunctionParameter>,
         body: Box<Stmt>,
     }, // fun func_name(...params) { ...stmt }
+    /// Declares a struct type and the names of its fields, so values of the
+    /// type can be created with a `StructInit`.
+    StructDeclaration {
+        name: String,
+        fields: Vec<String>,
+    }, // struct StructName { field, field }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
+    /// A number written with a decimal point or an exponent, like `5.0`, or a
+    /// whole number too big to fit in an `i64`.
     NumberLiteral(f64),
+    /// A whole number written without a decimal point or an exponent, like `5`.
+    IntegerLiteral(i64),
     StringLiteral(String),
     Identifier(String),
     GroupExpression(Box<Expr>),
@@ -69,16 +110,39 @@ pub enum Expr {
         func_name: String,
         arguments: Vec<FunctionArgument>,
     }, // func_name(...expr)
+    ArrayLiteral(Vec<Expr>), // [...expr]
+    /// Creates a map from keys to values. The keys have to be strings once
+    /// evaluated, and a key given twice keeps its last value.
+    MapLiteral(Vec<(Expr, Expr)>), // { expr = expr, expr = expr }
+    IndexExpression {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    }, // expr[expr]
+    /// Takes the elements of an array, or the characters of a string, from
+    /// `start` up to but not including `end`. Either bound can be left out.
+    SliceExpression {
+        target: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    }, // expr[expr:expr]
+    /// Creates a value of a declared struct type. Fields which aren't given
+    /// are nil.
+    StructInit {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    }, // StructName { field = expr, field = expr }
+    FieldAccess {
+        target: Box<Expr>,
+        field: String,
+    }, // expr.field
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone)]
 pub struct FunctionParameter {
-    name: String,
+    pub name: String,
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone)]
 pub struct FunctionArgument {
-    value: Expr,
+    pub value: Expr,
 }
@@ -1,9 +1,20 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Boolean {
     True,
     False,
 }
 
+impl From<bool> for Boolean {
+    fn from(value: bool) -> Self {
+        match value {
+            true => Boolean::True,
+            false => Boolean::False,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ComparisonOperator {
     LessThan,
@@ -15,3 +26,21 @@ pub enum ComparisonOperator {
     And,
     Or,
 }
+
+/// Shows the operator the way it is written in source code, like `<=`.
+impl Display for ComparisonOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let operator = match self {
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::EqualTo => "==",
+            Self::NotEqualTo => "!=",
+            Self::LessThanOrEqualTo => "<=",
+            Self::GreaterThanOrEqualTo => ">=",
+            Self::And => "and",
+            Self::Or => "or",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
@@ -0,0 +1,270 @@
+use crate::ast::*;
+use crate::common::*;
+
+/// Converts a whole program into a JSON array of statements, so tools outside
+/// of the interpreter (formatters, linters) can read the parsed program.
+/// Every node is an object with a `"type"` field naming its variant.
+pub fn program_to_json(program: &[Stmt]) -> String {
+    array(program.iter().map(Stmt::to_json))
+}
+
+impl Stmt {
+    /// Converts the statement into a JSON object.
+    pub fn to_json(&self) -> String {
+        match self {
+            Stmt::EmptyStatement => object("EmptyStatement", vec![]),
+            Stmt::Break => object("Break", vec![]),
+            Stmt::Continue => object("Continue", vec![]),
+            Stmt::BlockStatement(statements) => object(
+                "BlockStatement",
+                vec![("body", array(statements.iter().map(Stmt::to_json)))],
+            ),
+            Stmt::ExpressionStatement(expr) => {
+                object("ExpressionStatement", vec![("expression", expr.to_json())])
+            }
+            Stmt::LogStatement(args) => object(
+                "LogStatement",
+                vec![("arguments", array(args.iter().map(Expr::to_json)))],
+            ),
+            Stmt::LogErrStatement(args) => object(
+                "LogErrStatement",
+                vec![("arguments", array(args.iter().map(Expr::to_json)))],
+            ),
+            Stmt::ReturnStatement(expr) => {
+                object("ReturnStatement", vec![("value", expr.to_json())])
+            }
+            Stmt::IfStatement { condition, body } => object(
+                "IfStatement",
+                vec![("condition", condition.to_json()), ("body", body.to_json())],
+            ),
+            Stmt::ForLoop {
+                init,
+                condition,
+                step,
+                body,
+            } => object(
+                "ForLoop",
+                vec![
+                    ("init", init.to_json()),
+                    ("condition", condition.to_json()),
+                    ("step", step.to_json()),
+                    ("body", body.to_json()),
+                ],
+            ),
+            Stmt::VariableDeclaration { name, value } => object(
+                "VariableDeclaration",
+                vec![
+                    ("name", string(name)),
+                    (
+                        "value",
+                        value.as_ref().map_or("null".to_string(), Expr::to_json),
+                    ),
+                ],
+            ),
+            Stmt::ConstantDeclaration { name, value } => object(
+                "ConstantDeclaration",
+                vec![("name", string(name)), ("value", value.to_json())],
+            ),
+            Stmt::VariableReassignment { name, value } => object(
+                "VariableReassignment",
+                vec![("name", string(name)), ("value", value.to_json())],
+            ),
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+            } => object(
+                "FunctionDeclaration",
+                vec![
+                    ("name", string(name)),
+                    (
+                        "parameters",
+                        array(parameters.iter().map(|p| string(&p.name))),
+                    ),
+                    ("body", body.to_json()),
+                ],
+            ),
+            Stmt::StructDeclaration { name, fields } => object(
+                "StructDeclaration",
+                vec![
+                    ("name", string(name)),
+                    ("fields", array(fields.iter().map(|field| string(field)))),
+                ],
+            ),
+        }
+    }
+}
+
+impl Expr {
+    /// Converts the expression into a JSON object.
+    pub fn to_json(&self) -> String {
+        match self {
+            Expr::NumberLiteral(n) => object("NumberLiteral", vec![("value", number(*n))]),
+            Expr::IntegerLiteral(n) => object("IntegerLiteral", vec![("value", n.to_string())]),
+            Expr::StringLiteral(s) => object("StringLiteral", vec![("value", string(s))]),
+            Expr::Identifier(name) => object("Identifier", vec![("name", string(name))]),
+            Expr::GroupExpression(inner) => {
+                object("GroupExpression", vec![("expression", inner.to_json())])
+            }
+            Expr::UnaryExpression(operation, operand) => object(
+                "UnaryExpression",
+                vec![
+                    ("operator", string(&format!("{:?}", operation))),
+                    ("operand", operand.to_json()),
+                ],
+            ),
+            Expr::Boolean(b) => object(
+                "Boolean",
+                vec![(
+                    "value",
+                    match b {
+                        Boolean::True => "true",
+                        Boolean::False => "false",
+                    }
+                    .to_string(),
+                )],
+            ),
+            Expr::NilLiteral => object("NilLiteral", vec![]),
+            Expr::ComparisonExpression { lhs, rhs, operator } => object(
+                "ComparisonExpression",
+                vec![
+                    ("operator", string(&format!("{:?}", operator))),
+                    ("lhs", lhs.to_json()),
+                    ("rhs", rhs.to_json()),
+                ],
+            ),
+            Expr::BinaryExpression {
+                left_side,
+                right_side,
+                operator,
+            } => object(
+                "BinaryExpression",
+                vec![
+                    ("operator", string(&format!("{:?}", operator))),
+                    ("left_side", left_side.to_json()),
+                    ("right_side", right_side.to_json()),
+                ],
+            ),
+            Expr::FunctionCall {
+                func_name,
+                arguments,
+            } => object(
+                "FunctionCall",
+                vec![
+                    ("func_name", string(func_name)),
+                    (
+                        "arguments",
+                        array(arguments.iter().map(|arg| arg.value.to_json())),
+                    ),
+                ],
+            ),
+            Expr::ArrayLiteral(elements) => object(
+                "ArrayLiteral",
+                vec![("elements", array(elements.iter().map(Expr::to_json)))],
+            ),
+            Expr::MapLiteral(entries) => object(
+                "MapLiteral",
+                vec![(
+                    "entries",
+                    array(entries.iter().map(|(key, value)| {
+                        format!(
+                            "{{\"key\":{},\"value\":{}}}",
+                            key.to_json(),
+                            value.to_json()
+                        )
+                    })),
+                )],
+            ),
+            Expr::IndexExpression { target, index } => object(
+                "IndexExpression",
+                vec![("target", target.to_json()), ("index", index.to_json())],
+            ),
+            Expr::SliceExpression { target, start, end } => object(
+                "SliceExpression",
+                vec![
+                    ("target", target.to_json()),
+                    (
+                        "start",
+                        start.as_ref().map_or("null".to_string(), |e| e.to_json()),
+                    ),
+                    (
+                        "end",
+                        end.as_ref().map_or("null".to_string(), |e| e.to_json()),
+                    ),
+                ],
+            ),
+            Expr::StructInit { name, fields } => object(
+                "StructInit",
+                vec![
+                    ("name", string(name)),
+                    (
+                        "fields",
+                        format!(
+                            "{{{}}}",
+                            fields
+                                .iter()
+                                .map(|(field, value)| format!(
+                                    "{}:{}",
+                                    string(field),
+                                    value.to_json()
+                                ))
+                                .collect::<Vec<String>>()
+                                .join(",")
+                        ),
+                    ),
+                ],
+            ),
+            Expr::FieldAccess { target, field } => object(
+                "FieldAccess",
+                vec![("target", target.to_json()), ("field", string(field))],
+            ),
+        }
+    }
+}
+
+/// Builds a JSON object with a `"type"` of `kind`, followed by `fields`. The
+/// field values must already be JSON.
+fn object(kind: &str, fields: Vec<(&str, String)>) -> String {
+    let mut json = format!("{{\"type\":{}", string(kind));
+
+    for (key, value) in fields {
+        json.push_str(&format!(",{}:{}", string(key), value));
+    }
+
+    json.push('}');
+    json
+}
+
+/// Builds a JSON array out of values which are already JSON.
+fn array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<String>>().join(","))
+}
+
+/// JSON has no way of writing NaN or infinity, so they become null.
+fn number(n: f64) -> String {
+    if n.is_finite() {
+        format!("{}", n)
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Quotes `s` as a JSON string, escaping anything JSON doesn't allow as is.
+fn string(s: &str) -> String {
+    let mut json = String::from('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
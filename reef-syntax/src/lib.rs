@@ -1,3 +1,5 @@
 pub mod ast;
 pub mod common;
+pub mod json;
 pub mod token;
+pub mod tree;
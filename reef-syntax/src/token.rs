@@ -5,35 +5,89 @@ use std::fmt::{Display, Formatter, Result as FmtResult};
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Token<'a> {
     Comment(&'a str),
+    /// A run of whitespace, only produced when scanning with trivia.
+    Whitespace(&'a str),
     String(&'a str),
     Keyword(&'a str),
     Number(&'a str),
     Identifier(&'a str),
     Delimiter(char),                        // (, ), [, ], {, }, ;, :
     BinaryOperator(char),                   // +, -, /, *
+    BitwiseOperator(&'a str),               // &, |, ^, <<, >>
     ComparisonOperator(ComparisonOperator), // <, >, !=
     Illegal(char),
     Equals,
     EndOfFile,
 }
 
+impl Token<'_> {
+    /// Whether the token is a value on its own: a literal or an identifier.
+    pub fn is_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::String(_)
+                | Token::Identifier(_)
+                | Token::Keyword("true" | "false" | "nil")
+        )
+    }
+
+    /// Whether an expression can start with the token. This is every operand,
+    /// along with anything that comes before an operand, like `(` or `not`.
+    pub fn is_expression_start(&self) -> bool {
+        self.is_operand()
+            || matches!(
+                self,
+                Token::Delimiter('(' | '[' | '{')
+                    | Token::BinaryOperator('-')
+                    | Token::Keyword("not" | "typeof")
+            )
+    }
+
+    /// Whether the token is only there for formatting (whitespace or a
+    /// comment) and has no meaning to the parser.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Comment(_) | Token::Whitespace(_))
+    }
+}
+
 impl<'a> Display for Token<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "{:?}", self)
     }
 }
 
+/// The position in the source code where a token starts. Lines and columns
+/// both start counting from 1. `start` and `end` are the byte offsets of the
+/// token in the source, with `end` being exclusive.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Span {
+    pub line: u32,
+    pub col: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
 /// Wrapper type for Vec<Token> which allows it to be displayed.
-/// Used to print out token vectors and write them to files.
-pub struct TokenDisplay<'a>(pub &'a [Token<'a>]);
+/// Used to print out token vectors and write them to files. Each token is
+/// printed on its own line after the `line:col` it starts at, taken from the
+/// span at the same index.
+pub struct TokenDisplay<'a>(pub &'a [Token<'a>], pub &'a [Span]);
 
 impl<'a> Display for TokenDisplay<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        writeln!(f, "[")?;
-        for item in self.0 {
-            writeln!(f, "\t{},", item)?;
+        for (index, token) in self.0.iter().enumerate() {
+            match self.1.get(index) {
+                Some(span) => writeln!(f, "  {}:{}  {}", span.line, span.col, token)?,
+                None => writeln!(f, "  ?:?  {}", token)?,
+            }
         }
-        writeln!(f, "]")?;
 
         Ok(())
     }
@@ -0,0 +1,240 @@
+use crate::ast::*;
+use crate::common::*;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Renders a whole program as an indented tree, one statement after another.
+/// Every node is on its own line, with its children indented underneath it:
+///
+/// ```text
+/// ExpressionStatement
+///   BinaryExpression Plus
+///     IntegerLiteral 1
+///     IntegerLiteral 2
+/// ```
+pub fn program_to_tree(program: &[Stmt]) -> String {
+    let mut tree = String::new();
+
+    for stmt in program {
+        stmt_tree(stmt, 0, "", &mut tree);
+    }
+
+    tree
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut tree = String::new();
+        stmt_tree(self, 0, "", &mut tree);
+        write!(f, "{}", tree.trim_end())
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut tree = String::new();
+        expr_tree(self, 0, "", &mut tree);
+        write!(f, "{}", tree.trim_end())
+    }
+}
+
+/// Adds one line for a node to `tree`, indented two spaces per `depth`.
+/// `label` goes before the node, to say what part of its parent it is.
+fn line(tree: &mut String, depth: usize, label: &str, node: &str) {
+    tree.push_str(&"  ".repeat(depth));
+    tree.push_str(label);
+    tree.push_str(node);
+    tree.push('\n');
+}
+
+fn stmt_tree(stmt: &Stmt, depth: usize, label: &str, tree: &mut String) {
+    let children = depth + 1;
+
+    match stmt {
+        Stmt::EmptyStatement => line(tree, depth, label, "EmptyStatement"),
+        Stmt::Break => line(tree, depth, label, "Break"),
+        Stmt::Continue => line(tree, depth, label, "Continue"),
+        Stmt::BlockStatement(statements) => {
+            line(tree, depth, label, "BlockStatement");
+            for stmt in statements {
+                stmt_tree(stmt, children, "", tree);
+            }
+        }
+        Stmt::ExpressionStatement(expr) => {
+            line(tree, depth, label, "ExpressionStatement");
+            expr_tree(expr, children, "", tree);
+        }
+        Stmt::LogStatement(args) | Stmt::LogErrStatement(args) => {
+            let node = match stmt {
+                Stmt::LogErrStatement(_) => "LogErrStatement",
+                _ => "LogStatement",
+            };
+            line(tree, depth, label, node);
+            for arg in args {
+                expr_tree(arg, children, "", tree);
+            }
+        }
+        Stmt::ReturnStatement(expr) => {
+            line(tree, depth, label, "ReturnStatement");
+            expr_tree(expr, children, "", tree);
+        }
+        Stmt::IfStatement { condition, body } => {
+            line(tree, depth, label, "IfStatement");
+            expr_tree(condition, children, "condition: ", tree);
+            stmt_tree(body, children, "body: ", tree);
+        }
+        Stmt::ForLoop {
+            init,
+            condition,
+            step,
+            body,
+        } => {
+            line(tree, depth, label, "ForLoop");
+            stmt_tree(init, children, "init: ", tree);
+            expr_tree(condition, children, "condition: ", tree);
+            stmt_tree(step, children, "step: ", tree);
+            stmt_tree(body, children, "body: ", tree);
+        }
+        Stmt::VariableDeclaration { name, value } => {
+            line(tree, depth, label, &format!("VariableDeclaration {}", name));
+            if let Some(value) = value {
+                expr_tree(value, children, "", tree);
+            }
+        }
+        Stmt::ConstantDeclaration { name, value } => {
+            line(tree, depth, label, &format!("ConstantDeclaration {}", name));
+            expr_tree(value, children, "", tree);
+        }
+        Stmt::VariableReassignment { name, value } => {
+            line(
+                tree,
+                depth,
+                label,
+                &format!("VariableReassignment {}", name),
+            );
+            expr_tree(value, children, "", tree);
+        }
+        Stmt::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+        } => {
+            let parameters: Vec<&str> = parameters.iter().map(|p| p.name.as_str()).collect();
+            line(
+                tree,
+                depth,
+                label,
+                &format!("FunctionDeclaration {}({})", name, parameters.join(", ")),
+            );
+            stmt_tree(body, children, "", tree);
+        }
+        Stmt::StructDeclaration { name, fields } => line(
+            tree,
+            depth,
+            label,
+            &format!("StructDeclaration {} {{ {} }}", name, fields.join(", ")),
+        ),
+    }
+}
+
+fn expr_tree(expr: &Expr, depth: usize, label: &str, tree: &mut String) {
+    let children = depth + 1;
+
+    match expr {
+        Expr::NumberLiteral(n) => line(tree, depth, label, &format!("NumberLiteral {}", n)),
+        Expr::IntegerLiteral(n) => line(tree, depth, label, &format!("IntegerLiteral {}", n)),
+        Expr::StringLiteral(s) => line(tree, depth, label, &format!("StringLiteral {:?}", s)),
+        Expr::Identifier(name) => line(tree, depth, label, &format!("Identifier {}", name)),
+        Expr::Boolean(b) => {
+            let value = match b {
+                Boolean::True => "true",
+                Boolean::False => "false",
+            };
+            line(tree, depth, label, &format!("Boolean {}", value));
+        }
+        Expr::NilLiteral => line(tree, depth, label, "NilLiteral"),
+        Expr::GroupExpression(inner) => {
+            line(tree, depth, label, "GroupExpression");
+            expr_tree(inner, children, "", tree);
+        }
+        Expr::UnaryExpression(operation, operand) => {
+            line(
+                tree,
+                depth,
+                label,
+                &format!("UnaryExpression {:?}", operation),
+            );
+            expr_tree(operand, children, "", tree);
+        }
+        Expr::ComparisonExpression { lhs, rhs, operator } => {
+            line(
+                tree,
+                depth,
+                label,
+                &format!("ComparisonExpression {:?}", operator),
+            );
+            expr_tree(lhs, children, "", tree);
+            expr_tree(rhs, children, "", tree);
+        }
+        Expr::BinaryExpression {
+            left_side,
+            right_side,
+            operator,
+        } => {
+            line(
+                tree,
+                depth,
+                label,
+                &format!("BinaryExpression {:?}", operator),
+            );
+            expr_tree(left_side, children, "", tree);
+            expr_tree(right_side, children, "", tree);
+        }
+        Expr::FunctionCall {
+            func_name,
+            arguments,
+        } => {
+            line(tree, depth, label, &format!("FunctionCall {}", func_name));
+            for arg in arguments {
+                expr_tree(&arg.value, children, "", tree);
+            }
+        }
+        Expr::ArrayLiteral(elements) => {
+            line(tree, depth, label, "ArrayLiteral");
+            for element in elements {
+                expr_tree(element, children, "", tree);
+            }
+        }
+        Expr::MapLiteral(entries) => {
+            line(tree, depth, label, "MapLiteral");
+            for (key, value) in entries {
+                expr_tree(key, children, "key: ", tree);
+                expr_tree(value, children, "value: ", tree);
+            }
+        }
+        Expr::IndexExpression { target, index } => {
+            line(tree, depth, label, "IndexExpression");
+            expr_tree(target, children, "", tree);
+            expr_tree(index, children, "index: ", tree);
+        }
+        Expr::SliceExpression { target, start, end } => {
+            line(tree, depth, label, "SliceExpression");
+            expr_tree(target, children, "", tree);
+            if let Some(start) = start {
+                expr_tree(start, children, "start: ", tree);
+            }
+            if let Some(end) = end {
+                expr_tree(end, children, "end: ", tree);
+            }
+        }
+        Expr::StructInit { name, fields } => {
+            line(tree, depth, label, &format!("StructInit {}", name));
+            for (field, value) in fields {
+                expr_tree(value, children, &format!("{} = ", field), tree);
+            }
+        }
+        Expr::FieldAccess { target, field } => {
+            line(tree, depth, label, &format!("FieldAccess .{}", field));
+            expr_tree(target, children, "", tree);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use super::evaluator::Evaluator;
+use super::types::{RuntimeError, RuntimeType};
+use std::collections::HashMap;
+
+/// A function that is built into the interpreter rather than declared in reef
+/// code. It is given the evaluator which called it and the evaluated arguments.
+pub type BuiltinFunction =
+    fn(&mut Evaluator, Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError>;
+
+/// Creates the registry of every builtin function, keyed by the name used to
+/// call it.
+pub fn create_builtins() -> HashMap<&'static str, BuiltinFunction> {
+    let mut builtins: HashMap<&'static str, BuiltinFunction> = HashMap::new();
+
+    builtins.insert("assert", assert);
+    builtins.insert("builtins", builtins_list);
+    builtins.insert("format", format);
+    builtins.insert("len", len);
+    builtins.insert("lower", lower);
+    builtins.insert("num", num);
+    builtins.insert("read_line", read_line);
+    builtins.insert("split", split);
+    builtins.insert("str", str);
+    builtins.insert("substr", substr);
+    builtins.insert("trim", trim);
+    builtins.insert("upper", upper);
+
+    builtins
+}
+
+/// Errors if a builtin was called with the wrong number of arguments.
+fn expect_argument_count(
+    name: &str,
+    args: &[RuntimeType],
+    count: usize,
+) -> Result<(), RuntimeError> {
+    if args.len() != count {
+        return Err(RuntimeError::Generic(format!(
+            "{}() takes {} argument(s) but {} were given",
+            name,
+            count,
+            args.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Gets the string out of a builtin's argument, or errors naming the builtin
+/// if it isn't a string.
+fn expect_string<'a>(name: &str, arg: &'a RuntimeType) -> Result<&'a str, RuntimeError> {
+    match arg {
+        RuntimeType::String(s) => Ok(s),
+        other => Err(RuntimeError::Generic(format!(
+            "{}() expects a string, got a {}",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+/// `assert(condition)` or `assert(condition, message)` stops the program with
+/// an error if the condition isn't truthy, so a script can check itself.
+fn assert(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    if !(1..=2).contains(&args.len()) {
+        return Err(RuntimeError::Generic(format!(
+            "assert() takes 1 or 2 argument(s) but {} were given",
+            args.len()
+        )));
+    }
+
+    if args[0].is_truthy() {
+        return Ok(RuntimeType::Nil);
+    }
+
+    Err(RuntimeError::AssertionFailed(
+        args.get(1).map(|message| message.to_string()),
+    ))
+}
+
+/// `len(x)` returns how many characters are in a string, or how many elements
+/// are in an array or map. Anything else is a type error.
+fn len(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("len", &args, 1)?;
+
+    match &args[0] {
+        RuntimeType::String(s) => Ok(RuntimeType::Integer(s.chars().count() as i64)),
+        RuntimeType::Array(elements) => Ok(RuntimeType::Integer(elements.len() as i64)),
+        RuntimeType::Map(entries) => Ok(RuntimeType::Integer(entries.len() as i64)),
+        other => Err(RuntimeError::Generic(format!(
+            "len() expects a string, an array or a map, got a {}",
+            other.type_name()
+        ))),
+    }
+}
+
+/// `str(x)` converts any value into a string, the same way `log` shows it.
+fn str(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("str", &args, 1)?;
+
+    Ok(RuntimeType::String(args[0].to_string()))
+}
+
+/// `num(s)` parses a string into a number, ignoring whitespace around it. Like
+/// number literals, it is an integer unless it has a `.` or an exponent.
+/// Numbers are given back as they are.
+fn num(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("num", &args, 1)?;
+
+    match &args[0] {
+        n @ (RuntimeType::Number(_) | RuntimeType::Integer(_)) => Ok(n.clone()),
+        RuntimeType::String(s)
+            if !s.contains(['.', 'e', 'E']) && s.trim().parse::<i64>().is_ok() =>
+        {
+            Ok(RuntimeType::Integer(
+                s.trim().parse().expect("checked above"),
+            ))
+        }
+        RuntimeType::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            // Rust also parses "inf" and "NaN", which reef has no way to write.
+            .filter(|n| n.is_finite())
+            .map(RuntimeType::Number)
+            .ok_or_else(|| {
+                RuntimeError::Generic(format!("num() can't convert \"{}\" into a number", s))
+            }),
+        other => Err(RuntimeError::Generic(format!(
+            "num() expects a string, got a {}",
+            other.type_name()
+        ))),
+    }
+}
+
+/// `substr(s, start, end)` returns the characters of `s` from `start` up to,
+/// but not including, `end`.
+fn substr(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("substr", &args, 3)?;
+
+    let chars: Vec<char> = expect_string("substr", &args[0])?.chars().collect();
+
+    let mut bounds = [0; 2];
+    for (bound, arg) in bounds.iter_mut().zip(&args[1..]) {
+        *bound = match arg.as_integer() {
+            Some(i) => i,
+            None => {
+                return Err(RuntimeError::Generic(format!(
+                    "substr() expects whole numbers for start and end, got {}",
+                    arg
+                )))
+            }
+        };
+    }
+
+    let [start, end] = bounds;
+    for bound in bounds {
+        if bound < 0 || bound as usize > chars.len() {
+            return Err(RuntimeError::IndexOutOfBounds {
+                index: bound,
+                length: chars.len(),
+            });
+        }
+    }
+
+    if start > end {
+        return Err(RuntimeError::Generic(format!(
+            "substr() start ({}) is after its end ({})",
+            start, end
+        )));
+    }
+
+    Ok(RuntimeType::String(
+        chars[start as usize..end as usize].iter().collect(),
+    ))
+}
+
+/// `upper(s)` returns `s` with every letter in upper case.
+fn upper(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("upper", &args, 1)?;
+
+    Ok(RuntimeType::String(
+        expect_string("upper", &args[0])?.to_uppercase(),
+    ))
+}
+
+/// `lower(s)` returns `s` with every letter in lower case.
+fn lower(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("lower", &args, 1)?;
+
+    Ok(RuntimeType::String(
+        expect_string("lower", &args[0])?.to_lowercase(),
+    ))
+}
+
+/// `trim(s)` returns `s` without any whitespace at its start or end.
+fn trim(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("trim", &args, 1)?;
+
+    Ok(RuntimeType::String(
+        expect_string("trim", &args[0])?.trim().to_string(),
+    ))
+}
+
+/// `split(s, separator)` returns an array of the parts of `s` in between each
+/// `separator`. An empty separator splits `s` into its characters.
+fn split(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("split", &args, 2)?;
+
+    let s = expect_string("split", &args[0])?;
+    let separator = expect_string("split", &args[1])?;
+
+    let parts: Vec<RuntimeType> = if separator.is_empty() {
+        s.chars()
+            .map(|c| RuntimeType::String(c.to_string()))
+            .collect()
+    } else {
+        s.split(separator)
+            .map(|part| RuntimeType::String(part.to_string()))
+            .collect()
+    };
+
+    Ok(RuntimeType::Array(parts))
+}
+
+/// The most decimal places `format` can round a number to.
+const MAX_FORMAT_DIGITS: usize = 100;
+
+/// `format(x, digits)` returns the number `x` as a string, rounded to `digits`
+/// decimal places, so scripts can choose how precisely numbers are shown.
+fn format(_evaluator: &mut Evaluator, args: Vec<RuntimeType>) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("format", &args, 2)?;
+
+    let n = args[0].as_number().ok_or_else(|| {
+        RuntimeError::Generic(format!(
+            "format() expects a number, got a {}",
+            args[0].type_name()
+        ))
+    })?;
+
+    let digits = args[1]
+        .as_integer()
+        .and_then(|d| usize::try_from(d).ok())
+        .ok_or_else(|| {
+            RuntimeError::Generic(format!(
+                "format() expects a whole number of digits that isn't negative, got {}",
+                args[1]
+            ))
+        })?;
+
+    if digits > MAX_FORMAT_DIGITS {
+        return Err(RuntimeError::Generic(format!(
+            "format() can round to at most {} digits, got {}",
+            MAX_FORMAT_DIGITS, digits
+        )));
+    }
+
+    Ok(RuntimeType::String(format!("{:.*}", digits, n)))
+}
+
+/// `read_line()` reads the next line of input, without its line ending, or
+/// returns nil once the input has ended.
+fn read_line(
+    evaluator: &mut Evaluator,
+    args: Vec<RuntimeType>,
+) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("read_line", &args, 0)?;
+
+    match evaluator.read_input_line()? {
+        Some(line) => Ok(RuntimeType::String(line)),
+        None => Ok(RuntimeType::Nil),
+    }
+}
+
+/// `builtins()` returns an array with the name of every builtin function.
+fn builtins_list(
+    evaluator: &mut Evaluator,
+    args: Vec<RuntimeType>,
+) -> Result<RuntimeType, RuntimeError> {
+    expect_argument_count("builtins", &args, 0)?;
+
+    Ok(RuntimeType::Array(
+        evaluator
+            .builtin_names()
+            .into_iter()
+            .map(|name| RuntimeType::String(name.to_string()))
+            .collect(),
+    ))
+}
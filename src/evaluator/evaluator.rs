@@ -1,300 +1,1130 @@
 use colored::Colorize;
+use reef_core::{lex, parse};
 use reef_syntax::{
     ast::*,
     common::{Boolean, ComparisonOperator},
 };
-use std::fmt::{Display, Formatter, Result as FmtRes};
+use std::cell::{Ref, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display, Formatter, Result as FmtRes};
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::rc::Rc;
+use std::time::Instant;
 
+use super::builtins::{create_builtins, BuiltinFunction};
+use super::limits::{Resource, ResourceLimits};
 use super::types::*;
 
 /// The evaluator is the part of the interpreter that actually
 /// runs (evaluates) the code. It takes an input of statements
 /// and evaluates each statement as it reads it.
 #[derive(Debug)]
-pub struct Evaluator<'a> {
+pub struct Evaluator {
     pub program: Vec<Stmt>,
-    scope: Scope<'a>,
+    /// The innermost scope of whatever is currently being evaluated.
+    scope: Rc<RefCell<Scope>>,
     ptr: usize,
     _debug: u8,
+    /// Allows looser type coercions, such as booleans being used as numbers.
+    permissive: bool,
+    /// Whether the value of an expression statement like `1 + 1;` is written
+    /// to the output. Off by default, and turned on for the REPL.
+    echo_expressions: bool,
+    builtins: HashMap<&'static str, BuiltinFunction>,
+    /// Every struct type declared so far, with the names of its fields.
+    /// Struct types are global, wherever they are declared.
+    structs: HashMap<String, Vec<String>>,
+    limits: ResourceLimits,
+    /// How many statements and loop iterations have been run so far.
+    steps: u64,
+    /// How many function calls are currently in progress.
+    depth: usize,
+    /// When `evaluate_program` was last called, for the timeout.
+    started: Instant,
+    /// Where `log` statements and echoed values are written to.
+    output: Output,
+    /// Where `logerr` statements are written to.
+    error_output: Output,
+    /// Where `read_line()` reads from.
+    input: Input,
 }
 
-impl<'a> Display for Evaluator<'a> {
+/// Somewhere for program output to go. Wrapped up so that the evaluator can
+/// still be debug printed.
+struct Output {
+    writer: Box<dyn Write>,
+    /// How many bytes have been written so far, for the output limit.
+    bytes_written: usize,
+}
+
+impl Output {
+    fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer,
+            bytes_written: 0,
+        }
+    }
+
+    /// Writes a line to the writer. A line which would take it over
+    /// `max_bytes` isn't written at all.
+    fn write_line(&mut self, line: &str, max_bytes: Option<usize>) -> Result<(), RuntimeError> {
+        let bytes_written = self.bytes_written + line.len() + 1;
+
+        if max_bytes.is_some_and(|max| bytes_written > max) {
+            return Err(RuntimeError::ResourceExhausted(Resource::OutputBytes));
+        }
+
+        writeln!(self.writer, "{}", line)
+            .map_err(|e| RuntimeError::Generic(format!("Failed to write output: {}", e)))?;
+        self.bytes_written = bytes_written;
+
+        Ok(())
+    }
+}
+
+impl Debug for Output {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        write!(f, "Output")
+    }
+}
+
+/// Somewhere for program input to come from, or stdin when there is no
+/// reader. Stdin is read directly rather than through a reader of its own, so
+/// that no input is buffered up and lost once the evaluator is done with it.
+struct Input {
+    reader: Option<Box<dyn BufRead>>,
+}
+
+impl Input {
+    /// Reads the next line, without its line ending, or `None` at the end of
+    /// the input.
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        let read = match &mut self.reader {
+            Some(reader) => reader.read_line(&mut line)?,
+            None => io::stdin().read_line(&mut line)?,
+        };
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+impl Debug for Input {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        write!(f, "Input")
+    }
+}
+
+impl Display for Evaluator {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
         write!(
             f,
             "Evaluator<scope: {}, ptr: {}, _debug: {}, program: {:?}>",
-            self.scope, self.ptr, self._debug, self.program
+            self.scope.borrow(),
+            self.ptr,
+            self._debug,
+            self.program
         )
     }
 }
 
-impl<'a> Evaluator<'a> {
+impl Evaluator {
+    #[allow(unused)]
     pub fn new(program: Vec<Stmt>, debug: u8) -> Self {
+        Self::new_with_limits(program, debug, ResourceLimits::default())
+    }
+
+    /// Creates an evaluator which stops with a `ResourceExhausted` error once
+    /// the program goes over any of `limits`.
+    pub fn new_with_limits(program: Vec<Stmt>, debug: u8, limits: ResourceLimits) -> Self {
         Self {
             program,
-            scope: Scope::new(None),
+            scope: Rc::new(RefCell::new(Scope::new(None))),
             _debug: debug,
             ptr: 0,
+            permissive: false,
+            echo_expressions: false,
+            builtins: create_builtins(),
+            structs: HashMap::new(),
+            limits,
+            steps: 0,
+            depth: 0,
+            started: Instant::now(),
+            output: Output::new(Box::new(io::stdout())),
+            error_output: Output::new(Box::new(io::stderr())),
+            input: Input { reader: None },
         }
     }
 
+    /// Sends program output to `output` instead of stdout, so it can be
+    /// captured (e.g. by an embedder or a test).
+    #[allow(unused)]
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Output::new(output);
+    }
+
+    /// Sends `logerr` output to `output` instead of stderr.
+    #[allow(unused)]
+    pub fn set_error_output(&mut self, output: Box<dyn Write>) {
+        self.error_output = Output::new(output);
+    }
+
+    /// Reads `read_line()` input from `input` instead of stdin, so it can be
+    /// given by an embedder or a test.
     #[allow(unused)]
-    pub fn get_main_scope(&self) -> &Scope<'a> {
-        &self.scope
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = Input {
+            reader: Some(input),
+        };
+    }
+
+    /// Reads the next line of input for `read_line()`, or `None` once the
+    /// input has ended.
+    pub fn read_input_line(&mut self) -> Result<Option<String>, RuntimeError> {
+        self.input
+            .read_line()
+            .map_err(|e| RuntimeError::Generic(format!("Failed to read input: {}", e)))
+    }
+
+    /// Permissive mode lets booleans coerce to `1`/`0` in arithmetic.
+    pub fn set_permissive(&mut self, permissive: bool) {
+        self.permissive = permissive;
     }
 
-    pub fn evaluate_program(&mut self) {
-        while self.ptr < self.program.len() {
-            self.evaluate_statement(self.get_current_statement());
+    /// Echoing writes the value of every expression statement to the output,
+    /// rather than only running it.
+    #[allow(unused)]
+    pub fn set_echo_expressions(&mut self, echo_expressions: bool) {
+        self.echo_expressions = echo_expressions;
+    }
+
+    /// The names of every builtin function, in alphabetical order.
+    pub fn builtin_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.builtins.keys().copied().collect();
+        names.sort();
+        names
+    }
+
+    #[allow(unused)]
+    pub fn get_main_scope(&self) -> Ref<'_, Scope> {
+        self.scope.borrow()
+    }
+
+    /// A copy of every variable visible to the code currently being
+    /// evaluated, sorted by name, for debuggers and other tools. Evaluation
+    /// isn't affected by it.
+    #[allow(unused)]
+    pub fn variables(&self) -> Vec<(String, RuntimeType)> {
+        self.scope.borrow().visible_variables()
+    }
+
+    /// Evaluates every top level statement in order. A `return` at the top
+    /// level stops the program early, as does a runtime error. Top level
+    /// functions are hoisted, so they can be called before their declaration.
+    pub fn evaluate_program(&mut self) -> Result<(), RuntimeError> {
+        self.started = Instant::now();
+
+        let program = self.program[self.ptr..].to_vec();
+        self.hoist_functions(&program)?;
+
+        while let Some(statement) = self.get_current_statement() {
+            self.advance();
+
+            if let Stmt::FunctionDeclaration { .. } = statement {
+                continue;
+            }
+
+            match self.evaluate_statement(statement)? {
+                ControlFlow::Normal => {}
+                ControlFlow::Return(_) => break,
+                flow @ (ControlFlow::Break | ControlFlow::Continue) => {
+                    return Err(outside_loop(&flow))
+                }
+            }
         }
+
+        Ok(())
     }
 
-    fn evaluate_statement(&mut self, statement: Option<Stmt>) {
-        match statement {
-            Some(Stmt::ExpressionStatement(expr)) => self.evaluate_expression_statement(expr),
-            Some(Stmt::LogStatement(args)) => self.evaluate_log_statement(args),
-            Some(Stmt::IfStatement { condition, body }) => {
-                self.evaluate_if_statement(condition, body)
+    /// Scans, parses and evaluates `source_code` against the evaluator's
+    /// scope, so whatever earlier calls declared is still there. Gives back
+    /// the value of the last statement, which is `None` unless it was an
+    /// expression or a `return`. The value isn't echoed. Each call gets the
+    /// full step and time limits, as if it were a program of its own.
+    pub fn eval_str(&mut self, source_code: &str) -> Result<RuntimeType, ReefError> {
+        let mut scanner = lex::Scanner::new(source_code, self._debug);
+        if let Err(mut errors) = scanner.scan() {
+            return Err(errors.remove(0).into());
+        }
+
+        let mut parser = parse::Parser::new(scanner.tokens, scanner.spans, self._debug);
+        parser.parse_all()?;
+
+        self.started = Instant::now();
+        self.steps = 0;
+        self.hoist_functions(&parser.program)?;
+        let mut last = RuntimeType::None;
+
+        for statement in parser.program {
+            match statement {
+                Stmt::EmptyStatement => {}
+                Stmt::FunctionDeclaration { .. } => last = RuntimeType::None,
+                Stmt::ExpressionStatement(expr) => {
+                    self.step()?;
+                    last = self.evaluate_expression(expr)?;
+                }
+                statement => {
+                    last = RuntimeType::None;
+
+                    match self.evaluate_statement(statement)? {
+                        ControlFlow::Normal => {}
+                        ControlFlow::Return(v) => return Ok(v),
+                        flow @ (ControlFlow::Break | ControlFlow::Continue) => {
+                            return Err(outside_loop(&flow).into())
+                        }
+                    }
+                }
             }
-            Some(Stmt::VariableDeclaration { name, value }) => {
+        }
+
+        Ok(last)
+    }
+
+    /// Declares every function in `statements` up front, so that code can call
+    /// a function declared further down. Only the declarations themselves are
+    /// hoisted, not any other code, and not functions nested inside blocks.
+    /// The caller has to skip the declarations when it reaches them.
+    fn hoist_functions(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            if let Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+            } = statement
+            {
+                self.evaluate_function_declaration(
+                    name.clone(),
+                    parameters.clone(),
+                    *body.clone(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn evaluate_statement(&mut self, statement: Stmt) -> Result<ControlFlow, RuntimeError> {
+        self.step()?;
+
+        match statement {
+            Stmt::ExpressionStatement(expr) => self.evaluate_expression_statement(expr),
+            Stmt::LogStatement(args) => self.evaluate_log_statement(args, false),
+            Stmt::LogErrStatement(args) => self.evaluate_log_statement(args, true),
+            Stmt::IfStatement { condition, body } => self.evaluate_if_statement(condition, *body),
+            Stmt::VariableDeclaration { name, value } => {
                 self.evaluate_variable_declaration(name, value)
             }
-            Some(Stmt::VariableReassignment { name, value }) => {
+            Stmt::ConstantDeclaration { name, value } => {
+                self.evaluate_constant_declaration(name, value)
+            }
+            Stmt::VariableReassignment { name, value } => {
                 self.evaluate_variable_reassignment(name, value)
             }
-            Some(Stmt::BlockStatement(statements)) => self.evaluate_block_statement(statements),
-            Some(Stmt::EmptyStatement) => self.evaluate_empty_statement(),
-            Some(_stmt) => {
-                self.error(&format!("Unhandled statement {:?}", _stmt));
+            Stmt::ForLoop {
+                init,
+                condition,
+                step,
+                body,
+            } => self.evaluate_for_loop(*init, condition, *step, *body),
+            Stmt::BlockStatement(statements) => self.evaluate_in_child_scope(statements),
+            Stmt::ReturnStatement(expr) => self.evaluate_return_statement(expr),
+            Stmt::Break => Ok(ControlFlow::Break),
+            Stmt::Continue => Ok(ControlFlow::Continue),
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+            } => self.evaluate_function_declaration(name, parameters, *body),
+            Stmt::StructDeclaration { name, fields } => {
+                self.evaluate_struct_declaration(name, fields)
             }
-            None => RuntimeType::None,
-        };
+            Stmt::EmptyStatement => Ok(ControlFlow::Normal),
+        }
     }
 
-    fn evaluate_empty_statement(&mut self) -> RuntimeType {
-        self.advance();
-        RuntimeType::None
+    /// Evaluates an expression on its own. The value is echoed if echoing is
+    /// on (see `set_echo_expressions`), unless the expression is a function
+    /// call, which is only run for its side effects.
+    fn evaluate_expression_statement(&mut self, expr: Expr) -> Result<ControlFlow, RuntimeError> {
+        let is_call = matches!(expr, Expr::FunctionCall { .. });
+        let v = self.evaluate_expression(expr)?;
+
+        if self.echo_expressions && !is_call {
+            self.echo(v)?;
+        }
+
+        Ok(ControlFlow::Normal)
     }
 
-    fn evaluate_expression_statement(&mut self, expr: Expr) -> RuntimeType {
-        let v = self.evaluate_expression(expr);
-        self.log("expr_stmt", v);
-        self.advance();
-        RuntimeType::None
+    /// Writes `value` to the output the same way an echoed expression
+    /// statement is, e.g. for the REPL to show the value of an entry.
+    pub fn echo(&mut self, value: RuntimeType) -> Result<(), RuntimeError> {
+        self.log("expr_stmt", value)
     }
 
-    fn evaluate_expression(&mut self, expr: Expr) -> RuntimeType {
+    /// Declares a function as a variable in the current scope, which also
+    /// becomes the scope the function body can see.
+    fn evaluate_function_declaration(
+        &mut self,
+        name: String,
+        parameters: Vec<FunctionParameter>,
+        body: Stmt,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let body = match body {
+            Stmt::BlockStatement(statements) => statements,
+            _ => {
+                return Err(RuntimeError::Generic(
+                    "Expected a block statement as the function body".to_string(),
+                ))
+            }
+        };
+
+        let function = Function {
+            name: name.clone(),
+            parameters: parameters.into_iter().map(|p| p.name).collect(),
+            body,
+            closure: Rc::clone(&self.scope),
+        };
+
+        self.scope
+            .borrow_mut()
+            .set_variable(&name, RuntimeType::Function(Rc::new(function)))?;
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Evaluates the returned expression and hands it back up the call stack
+    /// so any enclosing blocks stop executing.
+    fn evaluate_return_statement(&mut self, expr: Expr) -> Result<ControlFlow, RuntimeError> {
+        let value = self.evaluate_expression(expr)?;
+        Ok(ControlFlow::Return(value))
+    }
+
+    /// Evaluates an expression to its value.
+    ///
+    /// Sub-expressions are always evaluated strictly left to right, so side
+    /// effects like logging from a function call happen in the order they are
+    /// written: both sides of an operator, function arguments, array elements,
+    /// map keys and values, and an index's target before the index. The only
+    /// exception is `and`/`or`, which may skip their right hand side.
+    fn evaluate_expression(&mut self, expr: Expr) -> Result<RuntimeType, RuntimeError> {
         match expr {
             Expr::BinaryExpression {
                 left_side,
                 right_side,
                 operator,
-            } => self.evaluate_binary_expression(left_side, right_side, operator),
+            } => self.evaluate_binary_expression(*left_side, *right_side, operator),
             Expr::ComparisonExpression { lhs, rhs, operator } => {
-                self.evaluate_comparison_expression(lhs, rhs, operator)
+                self.evaluate_comparison_expression(*lhs, *rhs, operator)
             }
-            Expr::UnaryExpression(_operation, expression) => {
-                let ret = self.evaluate_expression(*expression);
+            Expr::UnaryExpression(operation, expression) => {
+                let ret = self.evaluate_expression(*expression)?;
 
-                match ret {
-                    RuntimeType::Number(num) => RuntimeType::Number(-num),
-                    _ => self.error(&format!("Cant perform a unary operation on {:?}", ret)),
+                match (operation, ret) {
+                    (UnaryOperation::Minus, RuntimeType::Number(num)) => {
+                        Ok(RuntimeType::Number(-num))
+                    }
+                    // Only `i64::MIN` can't be negated as an integer.
+                    (UnaryOperation::Minus, RuntimeType::Integer(i)) => Ok(i
+                        .checked_neg()
+                        .map_or(RuntimeType::Number(-(i as f64)), RuntimeType::Integer)),
+                    (UnaryOperation::Not, ret) => {
+                        Ok(RuntimeType::Boolean(Boolean::from(!ret.is_truthy())))
+                    }
+                    (UnaryOperation::TypeOf, ret) => {
+                        Ok(RuntimeType::String(ret.type_name().to_string()))
+                    }
+                    (operation, ret) => Err(RuntimeError::Generic(format!(
+                        "Cant perform a unary {:?} operation on {:?}",
+                        operation, ret
+                    ))),
                 }
             }
             Expr::GroupExpression(expression) => self.evaluate_expression(*expression),
-            Expr::Boolean(boolean) => RuntimeType::Boolean(boolean),
-            Expr::NumberLiteral(n) => RuntimeType::Number(n),
-            Expr::StringLiteral(s) => RuntimeType::String(s),
-            Expr::Identifier(ident) => self.scope.get_variable(&ident),
-            _ => self.error(&format!("Unable to evaluate expression {:?}", expr)),
+            Expr::NilLiteral => Ok(RuntimeType::Nil),
+            Expr::Boolean(boolean) => Ok(RuntimeType::Boolean(boolean)),
+            Expr::NumberLiteral(n) => Ok(RuntimeType::Number(n)),
+            Expr::IntegerLiteral(n) => Ok(RuntimeType::Integer(n)),
+            Expr::StringLiteral(s) => {
+                self.check_string_length(&s)?;
+                Ok(RuntimeType::String(s))
+            }
+            Expr::Identifier(ident) => self.scope.borrow().get_variable(&ident),
+            Expr::FunctionCall {
+                func_name,
+                arguments,
+            } => self.evaluate_function_call(func_name, arguments),
+            Expr::ArrayLiteral(elements) => Ok(RuntimeType::Array(
+                elements
+                    .into_iter()
+                    .map(|element| self.evaluate_expression(element))
+                    .collect::<Result<Vec<RuntimeType>, RuntimeError>>()?,
+            )),
+            Expr::MapLiteral(entries) => self.evaluate_map_literal(entries),
+            Expr::IndexExpression { target, index } => {
+                self.evaluate_index_expression(*target, *index)
+            }
+            Expr::SliceExpression { target, start, end } => {
+                self.evaluate_slice_expression(*target, start.map(|e| *e), end.map(|e| *e))
+            }
+            Expr::StructInit { name, fields } => self.evaluate_struct_init(name, fields),
+            Expr::FieldAccess { target, field } => match self.evaluate_expression(*target)? {
+                RuntimeType::Struct { name, mut fields } => fields
+                    .remove(&field)
+                    .ok_or_else(|| no_such_field(&name, &field)),
+                other => Err(RuntimeError::Generic(format!(
+                    "Cannot get the field \"{}\" of a {}, only of a struct",
+                    field,
+                    other.type_name()
+                ))),
+            },
+        }
+    }
+
+    /// Declares a struct type. Declaring the same struct twice is an error.
+    fn evaluate_struct_declaration(
+        &mut self,
+        name: String,
+        fields: Vec<String>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        if self.structs.contains_key(&name) {
+            return Err(RuntimeError::Generic(format!(
+                "The struct {} has already been declared",
+                name
+            )));
+        }
+
+        self.structs.insert(name, fields);
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Creates a value of the struct type `name`. Any declared field which
+    /// isn't given a value is nil, and giving an undeclared field is an error.
+    fn evaluate_struct_init(
+        &mut self,
+        name: String,
+        fields: Vec<(String, Expr)>,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let mut values: BTreeMap<String, RuntimeType> = match self.structs.get(&name) {
+            Some(declared) => declared
+                .iter()
+                .map(|field| (field.clone(), RuntimeType::Nil))
+                .collect(),
+            None => {
+                return Err(RuntimeError::Generic(format!(
+                    "There is no struct called {}",
+                    name
+                )))
+            }
+        };
+
+        for (field, value) in fields {
+            if !values.contains_key(&field) {
+                return Err(no_such_field(&name, &field));
+            }
+
+            let value = self.evaluate_expression(value)?;
+            values.insert(field, value);
+        }
+
+        Ok(RuntimeType::Struct {
+            name,
+            fields: values,
+        })
+    }
+
+    /// Gets the element at `index` out of the array `target` evaluates to.
+    fn evaluate_index_expression(
+        &mut self,
+        target: Expr,
+        index: Expr,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let target = self.evaluate_expression(target)?;
+        let index = self.evaluate_expression(index)?;
+
+        // Maps are looked up by key rather than by position.
+        if let RuntimeType::Map(mut entries) = target {
+            return match index {
+                RuntimeType::String(key) => {
+                    entries.remove(&key).ok_or(RuntimeError::MissingKey(key))
+                }
+                _ => Err(RuntimeError::Generic(format!(
+                    "Maps can only be indexed with a string, got a {}",
+                    index.type_name()
+                ))),
+            };
+        }
+
+        let index = to_index(index)?;
+
+        match target {
+            RuntimeType::Array(elements) => resolve_index(index, elements.len())
+                .map(|i| elements[i].clone())
+                .ok_or(RuntimeError::IndexOutOfBounds {
+                    index,
+                    length: elements.len(),
+                }),
+            // Strings are indexed by character, giving a one character string.
+            RuntimeType::String(s) => {
+                let length = s.chars().count();
+                resolve_index(index, length)
+                    .and_then(|i| s.chars().nth(i))
+                    .map(|c| RuntimeType::String(c.to_string()))
+                    .ok_or(RuntimeError::IndexOutOfBounds { index, length })
+            }
+            _ => Err(RuntimeError::Generic(format!(
+                "Cannot index into {}",
+                target
+            ))),
+        }
+    }
+
+    /// Evaluates `target[start:end]`, giving a new array or string with the
+    /// elements or characters from `start` up to but not including `end`. A
+    /// missing start is the beginning and a missing end is the end. Negative
+    /// bounds count back from the end, like indexes do.
+    fn evaluate_slice_expression(
+        &mut self,
+        target: Expr,
+        start: Option<Expr>,
+        end: Option<Expr>,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let target = self.evaluate_expression(target)?;
+        let start = start.map(|e| self.evaluate_expression(e)).transpose()?;
+        let end = end.map(|e| self.evaluate_expression(e)).transpose()?;
+
+        let length = match &target {
+            RuntimeType::Array(elements) => elements.len(),
+            RuntimeType::String(s) => s.chars().count(),
+            _ => {
+                return Err(RuntimeError::Generic(format!(
+                    "Cannot slice {}, only arrays and strings",
+                    target
+                )))
+            }
+        };
+
+        let start = slice_bound(start, 0, length)?;
+        let end = slice_bound(end, length, length)?;
+
+        if start > end {
+            return Err(RuntimeError::Generic(format!(
+                "Slice start ({}) is after its end ({})",
+                start, end
+            )));
+        }
+
+        Ok(match target {
+            RuntimeType::Array(elements) => RuntimeType::Array(elements[start..end].to_vec()),
+            RuntimeType::String(s) => {
+                RuntimeType::String(s.chars().skip(start).take(end - start).collect())
+            }
+            _ => unreachable!("only arrays and strings have a length"),
+        })
+    }
+
+    /// Evaluates each key and value of a map literal in order. Keys have to be
+    /// strings, and a key given twice keeps its last value.
+    fn evaluate_map_literal(
+        &mut self,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let mut map = BTreeMap::new();
+
+        for (key, value) in entries {
+            let key = match self.evaluate_expression(key)? {
+                RuntimeType::String(key) => key,
+                other => {
+                    return Err(RuntimeError::Generic(format!(
+                        "Map keys have to be strings, got a {}",
+                        other.type_name()
+                    )))
+                }
+            };
+            let value = self.evaluate_expression(value)?;
+            map.insert(key, value);
+        }
+
+        Ok(RuntimeType::Map(map))
+    }
+
+    /// Evaluates each argument in order and then calls the function with them.
+    /// Functions declared in reef code take priority over builtins.
+    fn evaluate_function_call(
+        &mut self,
+        name: String,
+        arguments: Vec<FunctionArgument>,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let args = arguments
+            .into_iter()
+            .map(|arg| self.evaluate_expression(arg.value))
+            .collect::<Result<Vec<RuntimeType>, RuntimeError>>()?;
+
+        let declared = self.scope.borrow().get_variable(&name).ok();
+
+        match declared {
+            Some(RuntimeType::Function(function)) => self.call_function(&function, args),
+            Some(value) => Err(RuntimeError::Generic(format!(
+                "{} is {}, which isn't a function",
+                name, value
+            ))),
+            None => match self.builtins.get(name.as_str()).copied() {
+                Some(builtin) => builtin(self, args),
+                None => Err(RuntimeError::Generic(format!(
+                    "No function called {} exists",
+                    name
+                ))),
+            },
+        }
+    }
+
+    /// Runs the body of `function` in a new scope holding its arguments, and
+    /// gives back whatever it returned, or nil if it never returned.
+    fn call_function(
+        &mut self,
+        function: &Function,
+        args: Vec<RuntimeType>,
+    ) -> Result<RuntimeType, RuntimeError> {
+        if args.len() != function.parameters.len() {
+            return Err(RuntimeError::Generic(format!(
+                "{} takes {} argument(s) but was given {}",
+                function.name,
+                function.parameters.len(),
+                args.len()
+            )));
+        }
+
+        if self
+            .limits
+            .max_recursion_depth
+            .is_some_and(|max| self.depth >= max)
+        {
+            return Err(RuntimeError::ResourceExhausted(Resource::RecursionDepth));
+        }
+
+        let mut scope = Scope::new(Some(Rc::clone(&function.closure)));
+        for (parameter, value) in function.parameters.iter().zip(args) {
+            scope.set_variable(parameter, value)?;
+        }
+
+        self.depth += 1;
+        let result = self.evaluate_in_scope(Rc::new(RefCell::new(scope)), function.body.clone());
+        self.depth -= 1;
+
+        match result? {
+            ControlFlow::Return(value) => Ok(value),
+            ControlFlow::Normal => Ok(RuntimeType::Nil),
+            flow @ (ControlFlow::Break | ControlFlow::Continue) => Err(outside_loop(&flow)),
         }
     }
 
     /// Runs a variable declaration statement and adds the variable to the global
     /// `self.variables` field.
-    fn evaluate_variable_declaration(&mut self, name: String, value: Expr) -> RuntimeType {
-        let value = self.evaluate_expression(value);
-        self.scope.set_variable(&name, value);
-        self.advance();
-        RuntimeType::None
+    fn evaluate_variable_declaration(
+        &mut self,
+        name: String,
+        value: Option<Expr>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let value = match value {
+            Some(value) => self.evaluate_expression(value)?,
+            None => RuntimeType::Nil,
+        };
+        self.scope.borrow_mut().set_variable(&name, value)?;
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Declares a constant in the current scope, which can't be reassigned
+    /// afterwards.
+    fn evaluate_constant_declaration(
+        &mut self,
+        name: String,
+        value: Expr,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let value = self.evaluate_expression(value)?;
+        self.scope.borrow_mut().set_constant(&name, value)?;
+        Ok(ControlFlow::Normal)
     }
 
     /// Runs a variable declaration statement and adds the variable to the global
     /// `self.variables` field.
-    fn evaluate_variable_reassignment(&mut self, name: String, value: Expr) -> RuntimeType {
-        let value = self.evaluate_expression(value);
-        self.scope.reassign_variable(&name, value);
-        self.advance();
-        RuntimeType::None
-    }
-
-    fn evaluate_if_statement(&mut self, condition: Expr, body: Box<Stmt>) -> RuntimeType {
-        let c = self.evaluate_expression(condition);
-
-        match c {
-            RuntimeType::Boolean(b) => match b {
-                Boolean::True => {
-                    self.evaluate_block_statement(match *body {
-                        Stmt::BlockStatement(statements) => statements,
-                        _ => self
-                            .error("Expected a block statement following if statement condition"),
-                    });
-                }
-                Boolean::False => self.advance(),
-            },
-            _ => self.error("If statement condition didnt evaluate to a boolean"),
+    fn evaluate_variable_reassignment(
+        &mut self,
+        name: String,
+        value: Expr,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let value = self.evaluate_expression(value)?;
+        self.scope.borrow_mut().reassign_variable(&name, value)?;
+        Ok(ControlFlow::Normal)
+    }
+
+    fn evaluate_if_statement(
+        &mut self,
+        condition: Expr,
+        body: Stmt,
+    ) -> Result<ControlFlow, RuntimeError> {
+        if !self.evaluate_expression(condition)?.is_truthy() {
+            return Ok(ControlFlow::Normal);
         }
 
-        RuntimeType::None
+        match body {
+            Stmt::BlockStatement(statements) => self.evaluate_in_child_scope(statements),
+            _ => Err(RuntimeError::Generic(
+                "Expected a block statement following if statement condition".to_string(),
+            )),
+        }
     }
 
     fn evaluate_comparison_expression(
         &mut self,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
+        lhs: Expr,
+        rhs: Expr,
         operator: ComparisonOperator,
-    ) -> RuntimeType {
-        let lhs = self.evaluate_expression(*lhs);
-        let rhs = self.evaluate_expression(*rhs);
+    ) -> Result<RuntimeType, RuntimeError> {
+        let lhs = self.evaluate_expression(lhs)?;
 
+        // `and`/`or` short circuit, so the right hand side is only evaluated
+        // when the left hand side doesn't already decide the result.
         match operator {
             ComparisonOperator::And => {
-                let lhs_v = match lhs {
-                    RuntimeType::Boolean(b) => b,
-                    _ => self.error(
-                        "Expected both sides of comparison expression to evaluate to a boolean",
-                    ),
-                };
-
-                let rhs_v = match rhs {
-                    RuntimeType::Boolean(b) => b,
-                    _ => self.error(
-                        "Expected both sides of comparison expression to evaluate to a boolean",
-                    ),
-                };
-
-                if lhs_v == Boolean::True && rhs_v == Boolean::True {
-                    RuntimeType::Boolean(Boolean::True)
-                } else {
-                    RuntimeType::Boolean(Boolean::False)
-                }
+                let result = lhs.is_truthy() && self.evaluate_expression(rhs)?.is_truthy();
+                return Ok(RuntimeType::Boolean(Boolean::from(result)));
             }
             ComparisonOperator::Or => {
-                let lhs_v = match lhs {
-                    RuntimeType::Boolean(b) => b,
-                    _ => self.error(
-                        "Expected both sides of comparison expression to evaluate to a boolean",
-                    ),
-                };
-
-                let rhs_v = match rhs {
-                    RuntimeType::Boolean(b) => b,
-                    _ => self.error(
-                        "Expected both sides of comparison expression to evaluate to a boolean",
-                    ),
-                };
-
-                if lhs_v == Boolean::True || rhs_v == Boolean::True {
-                    RuntimeType::Boolean(Boolean::True)
-                } else {
-                    RuntimeType::Boolean(Boolean::False)
-                }
+                let result = lhs.is_truthy() || self.evaluate_expression(rhs)?.is_truthy();
+                return Ok(RuntimeType::Boolean(Boolean::from(result)));
+            }
+            _ => {}
+        }
+
+        let rhs = self.evaluate_expression(rhs)?;
+
+        match operator {
+            ComparisonOperator::And | ComparisonOperator::Or => {
+                unreachable!("and/or are evaluated above")
             }
-            ComparisonOperator::EqualTo => RuntimeType::Boolean(match lhs == rhs {
+            ComparisonOperator::EqualTo => Ok(RuntimeType::Boolean(match lhs == rhs {
                 true => Boolean::True,
                 false => Boolean::False,
-            }),
-            ComparisonOperator::NotEqualTo => RuntimeType::Boolean(match lhs != rhs {
+            })),
+            ComparisonOperator::NotEqualTo => Ok(RuntimeType::Boolean(match lhs != rhs {
                 true => Boolean::True,
                 false => Boolean::False,
-            }),
-            ComparisonOperator::GreaterThan => self.error("Greater than is not implemented"),
-            ComparisonOperator::LessThan => self.error("Less than is not implemented"),
-            ComparisonOperator::LessThanOrEqualTo => {
-                self.error("Less than/equal to is not implemented")
-            }
-            ComparisonOperator::GreaterThanOrEqualTo => {
-                self.error("Greater than/equal to is not implemented")
-            }
+            })),
+            ComparisonOperator::GreaterThan
+            | ComparisonOperator::LessThan
+            | ComparisonOperator::LessThanOrEqualTo
+            | ComparisonOperator::GreaterThanOrEqualTo => compare_numbers(lhs, rhs, operator),
         }
     }
 
-    fn evaluate_block_statement(&mut self, statements: Vec<Stmt>) -> RuntimeType {
-        for statement in statements {
-            self.evaluate_statement(Some(statement));
-        }
+    /// Runs `init` once, then the body and `step` for as long as `condition`
+    /// is true. The loop has a scope of its own, so a variable declared by
+    /// `init` is gone once the loop has finished, and each iteration of the
+    /// body gets a fresh scope inside of it.
+    fn evaluate_for_loop(
+        &mut self,
+        init: Stmt,
+        condition: Expr,
+        step: Stmt,
+        body: Stmt,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let loop_scope = Rc::new(RefCell::new(Scope::new(Some(Rc::clone(&self.scope)))));
+        let previous = mem::replace(&mut self.scope, loop_scope);
+
+        let result = self.evaluate_loop_in_current_scope(init, condition, step, body);
 
-        RuntimeType::None
+        self.scope = previous;
+        result
     }
 
-    /// Runs a log statement, printing all of its arguments one after another in
-    /// one string.
-    fn evaluate_log_statement(&mut self, args: Vec<Expr>) -> RuntimeType {
-        let mut val_to_print = String::new();
+    fn evaluate_loop_in_current_scope(
+        &mut self,
+        init: Stmt,
+        condition: Expr,
+        step: Stmt,
+        body: Stmt,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let statements = match body {
+            Stmt::BlockStatement(statements) => statements,
+            _ => {
+                return Err(RuntimeError::Generic(
+                    "Expected a block statement following for loop condition".to_string(),
+                ))
+            }
+        };
 
-        let mut ptr = 0;
-        while ptr < args.len() {
-            let expr = self.evaluate_expression(args.get(ptr).unwrap().clone());
+        self.evaluate_statement(init)?;
 
-            if ptr == args.len() - 1 {
-                val_to_print.push_str(&format!("{}", expr));
-            } else {
-                val_to_print.push_str(&format!("{} ", expr));
+        loop {
+            self.step()?;
+
+            if !self.evaluate_expression(condition.clone())?.is_truthy() {
+                break;
             }
 
-            ptr += 1;
+            match self.evaluate_in_child_scope(statements.clone())? {
+                // The step still runs after `continue`.
+                ControlFlow::Normal | ControlFlow::Continue => {}
+                ControlFlow::Break => break,
+                ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+            }
+
+            self.evaluate_statement(step.clone())?;
         }
 
-        println!("{}", val_to_print);
+        Ok(ControlFlow::Normal)
+    }
 
-        self.advance();
+    /// Runs `statements` inside a new scope whose parent is the current one, so
+    /// anything declared in them is gone once they have finished, and can
+    /// shadow variables from outside.
+    fn evaluate_in_child_scope(
+        &mut self,
+        statements: Vec<Stmt>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let child = Rc::new(RefCell::new(Scope::new(Some(Rc::clone(&self.scope)))));
+        self.evaluate_in_scope(child, statements)
+    }
+
+    /// Runs `statements` inside `scope`, going back to the current scope
+    /// afterwards even if evaluation failed.
+    fn evaluate_in_scope(
+        &mut self,
+        scope: Rc<RefCell<Scope>>,
+        statements: Vec<Stmt>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let previous = mem::replace(&mut self.scope, scope);
 
-        RuntimeType::None
+        let result = self.evaluate_block_statement(statements);
+
+        self.scope = previous;
+        result
+    }
+
+    /// Runs each statement in the block, stopping early if one of them
+    /// returns or breaks so it can propagate to the enclosing block/function.
+    fn evaluate_block_statement(
+        &mut self,
+        statements: Vec<Stmt>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        for statement in statements {
+            match self.evaluate_statement(statement)? {
+                ControlFlow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Runs a log statement, printing all of its arguments in one line with a
+    /// single space between each of them. `logerr` statements go to the
+    /// error output instead.
+    fn evaluate_log_statement(
+        &mut self,
+        args: Vec<Expr>,
+        to_stderr: bool,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let values = args
+            .into_iter()
+            .map(|arg| Ok(self.evaluate_expression(arg)?.to_string()))
+            .collect::<Result<Vec<String>, RuntimeError>>()?;
+        let line = values.join(" ");
+
+        if to_stderr {
+            self.error_output
+                .write_line(&line, self.limits.max_output_bytes)?;
+        } else {
+            self.write_line(&line)?;
+        }
+
+        Ok(ControlFlow::Normal)
     }
 
     /// Evaluates the value of a binary expression. For example 1 + 2 will
-    /// evaluate to the runtime value of Number(3).
+    /// evaluate to the runtime value of Integer(3). `%` is a modulo rather than
+    /// a remainder, see `floored_modulo`. Multiplying a string by a number
+    /// repeats the string.
+    ///
+    /// Numbers are either integers or floats, and the result is:
+    ///
+    /// - an integer when both sides are integers. `/` rounds down, so `5 / 2`
+    ///   is `2` and `-5 / 2` is `-3`, which goes with `%` taking the sign of
+    ///   the divisor.
+    /// - a float when either side is a float, after turning the other side
+    ///   into a float, so `5.0 / 2` is `2.5`.
+    /// - a float when both sides are integers but the result is too big for
+    ///   an integer.
+    /// - always an integer for the bitwise operators, which also take floats
+    ///   as long as they are whole numbers.
+    ///
+    /// In permissive mode, booleans are the integers `1` and `0`.
     fn evaluate_binary_expression(
         &mut self,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
+        lhs: Expr,
+        rhs: Expr,
         operator: BinaryExprOperator,
-    ) -> RuntimeType {
-        let lhs = self.evaluate_expression(*lhs);
-        let rhs = self.evaluate_expression(*rhs);
-
-        // Undefined because the actual values are set later.
-        let lhs_n: f64;
-        let rhs_n: f64;
-        let final_num: f64;
-
-        match lhs {
-            RuntimeType::Number(n) => lhs_n = n,
-            _ => self.error("Cannot perform binary operations on anything that isnt a number"),
-        };
+    ) -> Result<RuntimeType, RuntimeError> {
+        // The left hand side is always evaluated first, see
+        // `evaluate_expression`.
+        let lhs = self.evaluate_expression(lhs)?;
+        let rhs = self.evaluate_expression(rhs)?;
+
+        if operator == BinaryExprOperator::Multiply {
+            if let (RuntimeType::String(s), count) | (count, RuntimeType::String(s)) = (&lhs, &rhs)
+            {
+                if let Some(count) = count.as_number() {
+                    return self.repeat_string(s, count);
+                }
+            }
+        }
+
+        let lhs = self.to_arithmetic_operand(lhs)?;
+        let rhs = self.to_arithmetic_operand(rhs)?;
+
+        if let BinaryExprOperator::BitwiseAnd
+        | BinaryExprOperator::BitwiseOr
+        | BinaryExprOperator::BitwiseXor
+        | BinaryExprOperator::ShiftLeft
+        | BinaryExprOperator::ShiftRight = operator
+        {
+            return Ok(RuntimeType::Integer(bitwise(&lhs, &rhs, operator)?));
+        }
+
+        if let (RuntimeType::Integer(l), RuntimeType::Integer(r)) = (&lhs, &rhs) {
+            if let Some(result) = integer_arithmetic(*l, *r, &operator)? {
+                return Ok(RuntimeType::Integer(result));
+            }
+        }
 
-        match rhs {
-            RuntimeType::Number(n) => rhs_n = n,
-            _ => self.error("Cannot perform binary operations on anything that isnt a number"),
+        let (lhs_n, rhs_n) = match (lhs.as_number(), rhs.as_number()) {
+            (Some(l), Some(r)) => (l, r),
+            _ => unreachable!("arithmetic operands are always numbers"),
         };
 
-        match operator {
-            BinaryExprOperator::Plus => final_num = lhs_n + rhs_n,
-            BinaryExprOperator::Minus => final_num = lhs_n - rhs_n,
-            BinaryExprOperator::Multiply => final_num = lhs_n * rhs_n,
-            BinaryExprOperator::Divide => final_num = lhs_n / rhs_n,
-            BinaryExprOperator::Modulus => final_num = lhs_n % rhs_n,
+        let final_num = match operator {
+            BinaryExprOperator::Plus => lhs_n + rhs_n,
+            BinaryExprOperator::Minus => lhs_n - rhs_n,
+            BinaryExprOperator::Multiply => lhs_n * rhs_n,
+            BinaryExprOperator::Divide | BinaryExprOperator::Modulus if rhs_n == 0.0 => {
+                return Err(RuntimeError::DivisionByZero);
+            }
+            BinaryExprOperator::Divide => lhs_n / rhs_n,
+            BinaryExprOperator::Modulus => floored_modulo(lhs_n, rhs_n),
+            _ => unreachable!("bitwise operators are evaluated above"),
         };
 
-        RuntimeType::Number(final_num)
+        Ok(RuntimeType::Number(final_num))
     }
 
-    fn log(&self, source: &str, value: RuntimeType) {
-        println!("{}", format!("[{}] {}", source, value).bright_green());
+    /// `string * count`. The count has to be a whole number which isn't
+    /// negative, and the result has to fit in the string length limit.
+    fn repeat_string(&self, string: &str, count: f64) -> Result<RuntimeType, RuntimeError> {
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(RuntimeError::Generic(format!(
+                "A string can only be repeated a whole number of times that isn't negative, not {}",
+                count
+            )));
+        }
+
+        // Checked before the string is built, so a huge count can't use up
+        // all of the memory first. No string can be longer than `isize::MAX`
+        // bytes, whatever the limits are.
+        let too_long = RuntimeError::ResourceExhausted(Resource::StringLength);
+        if count > usize::MAX as f64 {
+            return Err(too_long);
+        }
+        let count = count as usize;
+        match string.len().checked_mul(count) {
+            Some(length) if length <= isize::MAX as usize => {}
+            _ => return Err(too_long),
+        }
+
+        if let Some(max) = self.limits.max_string_length {
+            if string.chars().count().saturating_mul(count) > max {
+                return Err(too_long);
+            }
+        }
+
+        Ok(RuntimeType::String(string.repeat(count)))
     }
 
-    fn error(&self, value: &str) -> ! {
-        panic!("{}", format!("[error] {}", value).bright_red());
+    /// Checks a binary expression operand is a number. Booleans are only
+    /// allowed in permissive mode, where `true` is `1` and `false` is `0`.
+    fn to_arithmetic_operand(&self, value: RuntimeType) -> Result<RuntimeType, RuntimeError> {
+        match value {
+            RuntimeType::Number(_) | RuntimeType::Integer(_) => Ok(value),
+            RuntimeType::Boolean(b) if self.permissive => match b {
+                Boolean::True => Ok(RuntimeType::Integer(1)),
+                Boolean::False => Ok(RuntimeType::Integer(0)),
+            },
+            _ => Err(RuntimeError::Generic(
+                "Cannot perform binary operations on anything that isnt a number".to_string(),
+            )),
+        }
+    }
+
+    /// Counts one more step towards the step limit, and checks the program
+    /// hasn't been running for longer than it is allowed to.
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+
+        if self.limits.max_steps.is_some_and(|max| self.steps > max) {
+            return Err(RuntimeError::ResourceExhausted(Resource::Steps));
+        }
+
+        if self
+            .limits
+            .timeout
+            .is_some_and(|timeout| self.started.elapsed() > timeout)
+        {
+            return Err(RuntimeError::ResourceExhausted(Resource::Time));
+        }
+
+        Ok(())
+    }
+
+    /// Errors if `string` is longer than the string length limit allows.
+    fn check_string_length(&self, string: &str) -> Result<(), RuntimeError> {
+        match self.limits.max_string_length {
+            Some(max) if string.chars().count() > max => {
+                Err(RuntimeError::ResourceExhausted(Resource::StringLength))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn log(&mut self, source: &str, value: RuntimeType) -> Result<(), RuntimeError> {
+        let line = format!("[{}] {}", source, value).bright_green().to_string();
+        self.write_line(&line)
+    }
+
+    /// Writes a line of program output. A line which would take the output
+    /// over its limit isn't written at all.
+    fn write_line(&mut self, line: &str) -> Result<(), RuntimeError> {
+        self.output.write_line(line, self.limits.max_output_bytes)
     }
 
     fn get_current_statement(&self) -> Option<Stmt> {
@@ -310,12 +1140,198 @@ impl<'a> Evaluator<'a> {
     }
 }
 
-/// Converts an expression to a boolean value. Useful for
-/// comparison expressions which require both sides to be
-/// booleans.
-fn expr_to_boolean(expr: &Expr) -> Boolean {
-    match *expr {
-        Expr::NilLiteral | Expr::Boolean(Boolean::False) => Boolean::False,
-        _ => Boolean::True,
+/// The parser only allows `break` and `continue` inside of loops, but a
+/// program built by hand could still have them anywhere.
+fn outside_loop(flow: &ControlFlow) -> RuntimeError {
+    let keyword = match flow {
+        ControlFlow::Continue => "continue",
+        _ => "break",
+    };
+
+    RuntimeError::Generic(format!("`{}` can only be used inside of a loop", keyword))
+}
+
+/// The error for a struct field which the struct's type doesn't declare.
+fn no_such_field(struct_name: &str, field: &str) -> RuntimeError {
+    RuntimeError::Generic(format!(
+        "The struct {} has no field called \"{}\"",
+        struct_name, field
+    ))
+}
+
+/// Compares two numbers with one of the ordering comparison operators. Any
+/// other types are a `TypeMismatch`.
+fn compare_numbers(
+    lhs: RuntimeType,
+    rhs: RuntimeType,
+    operator: ComparisonOperator,
+) -> Result<RuntimeType, RuntimeError> {
+    // Integers are compared as they are, so big ones aren't rounded first.
+    let ordering = match (&lhs, &rhs) {
+        (RuntimeType::Integer(l), RuntimeType::Integer(r)) => l.partial_cmp(r),
+        _ => match (lhs.as_number(), rhs.as_number()) {
+            (Some(l), Some(r)) => l.partial_cmp(&r),
+            _ => {
+                return Err(RuntimeError::TypeMismatch {
+                    operator,
+                    lhs: lhs.type_name(),
+                    rhs: rhs.type_name(),
+                })
+            }
+        },
+    };
+
+    // NaN isn't ordered, so every comparison with it is false.
+    let result = match operator {
+        ComparisonOperator::GreaterThan => ordering == Some(Ordering::Greater),
+        ComparisonOperator::LessThan => ordering == Some(Ordering::Less),
+        ComparisonOperator::LessThanOrEqualTo => {
+            matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+        }
+        ComparisonOperator::GreaterThanOrEqualTo => {
+            matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+        }
+        _ => unreachable!("only ordering operators compare numbers"),
+    };
+
+    Ok(RuntimeType::Boolean(Boolean::from(result)))
+}
+
+/// Runs an arithmetic operator on two integers. Returns `None` if the result
+/// doesn't fit in an integer, so it can be worked out with floats instead.
+fn integer_arithmetic(
+    lhs: i64,
+    rhs: i64,
+    operator: &BinaryExprOperator,
+) -> Result<Option<i64>, RuntimeError> {
+    Ok(match operator {
+        BinaryExprOperator::Plus => lhs.checked_add(rhs),
+        BinaryExprOperator::Minus => lhs.checked_sub(rhs),
+        BinaryExprOperator::Multiply => lhs.checked_mul(rhs),
+        BinaryExprOperator::Divide | BinaryExprOperator::Modulus if rhs == 0 => {
+            return Err(RuntimeError::DivisionByZero);
+        }
+        // Rounds down rather than towards zero, like `floored_modulo`.
+        BinaryExprOperator::Divide => lhs.checked_div(rhs).map(|quotient| {
+            if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }),
+        BinaryExprOperator::Modulus => lhs.checked_rem(rhs).map(|remainder| {
+            if remainder != 0 && (remainder < 0) != (rhs < 0) {
+                remainder + rhs
+            } else {
+                remainder
+            }
+        }),
+        _ => unreachable!("{:?} isn't an arithmetic operator", operator),
+    })
+}
+
+/// Runs one of the bitwise operators on two whole numbers, treating them as
+/// 64 bit integers. A shift has to be by 0 to 63 bits.
+fn bitwise(
+    lhs: &RuntimeType,
+    rhs: &RuntimeType,
+    operator: BinaryExprOperator,
+) -> Result<i64, RuntimeError> {
+    let (l, r) = (to_integer(lhs)?, to_integer(rhs)?);
+
+    let result = match operator {
+        BinaryExprOperator::BitwiseAnd => l & r,
+        BinaryExprOperator::BitwiseOr => l | r,
+        BinaryExprOperator::BitwiseXor => l ^ r,
+        BinaryExprOperator::ShiftLeft | BinaryExprOperator::ShiftRight => {
+            let bits = u32::try_from(r)
+                .ok()
+                .filter(|bits| *bits < 64)
+                .ok_or_else(|| {
+                    RuntimeError::Generic(format!("Cannot shift by {} bits, only by 0 to 63", r))
+                })?;
+
+            if operator == BinaryExprOperator::ShiftLeft {
+                l << bits
+            } else {
+                l >> bits
+            }
+        }
+        _ => unreachable!("{:?} isn't a bitwise operator", operator),
+    };
+
+    Ok(result)
+}
+
+/// Turns a number into an integer for the bitwise operators, as long as it is
+/// a whole number which fits.
+fn to_integer(n: &RuntimeType) -> Result<i64, RuntimeError> {
+    n.as_integer().ok_or_else(|| {
+        RuntimeError::Generic(format!(
+            "Bitwise operators only work on whole numbers, not {}",
+            n
+        ))
+    })
+}
+
+/// Checks that `index` is a whole number, which is all that can index an
+/// array or string.
+fn to_index(index: RuntimeType) -> Result<i64, RuntimeError> {
+    index.as_integer().ok_or_else(|| {
+        RuntimeError::Generic(format!(
+            "Expected a whole number to index with, got {}",
+            index
+        ))
+    })
+}
+
+/// Turns `index` into a position in a collection of `length`, where negative
+/// indexes count back from the end so `-1` is the last element. Returns `None`
+/// if that's outside of the collection.
+fn resolve_index(index: i64, length: usize) -> Option<usize> {
+    let index = if index < 0 {
+        index + length as i64
+    } else {
+        index
+    };
+
+    usize::try_from(index).ok().filter(|&i| i < length)
+}
+
+/// Turns a slice bound into a position in a collection of `length`, using
+/// `default` if the bound was left out. A bound can be anywhere from the start
+/// to just past the end, and negative bounds count back from the end.
+fn slice_bound(
+    bound: Option<RuntimeType>,
+    default: usize,
+    length: usize,
+) -> Result<usize, RuntimeError> {
+    let index = match bound {
+        Some(bound) => to_index(bound)?,
+        None => return Ok(default),
+    };
+
+    let resolved = if index < 0 {
+        index + length as i64
+    } else {
+        index
+    };
+
+    match usize::try_from(resolved) {
+        Ok(resolved) if resolved <= length => Ok(resolved),
+        _ => Err(RuntimeError::IndexOutOfBounds { index, length }),
+    }
+}
+
+/// `a % b` as a mathematical modulo, where the result takes the sign of the
+/// divisor like in Python, so `-1 % 3` is `2` and `7 % -3` is `-2`. Rust's `%`
+/// is a remainder instead, which takes the sign of the dividend.
+fn floored_modulo(a: f64, b: f64) -> f64 {
+    let remainder = a % b;
+
+    if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+        remainder + b
+    } else {
+        remainder
     }
 }
@@ -0,0 +1,40 @@
+use std::fmt::{Display, Formatter, Result as FmtRes};
+use std::time::Duration;
+
+/// Caps on how much work a program is allowed to do, for running code which
+/// can't be trusted to finish. Every limit is off (`None`) by default.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// The most statements and loop iterations that can be run.
+    pub max_steps: Option<u64>,
+    /// The most function calls that can be in progress at once.
+    pub max_recursion_depth: Option<usize>,
+    /// The longest a string can be, in characters.
+    pub max_string_length: Option<usize>,
+    /// How long the program can run for before it is stopped.
+    pub timeout: Option<Duration>,
+    /// The most bytes of output the program can write, newlines included.
+    pub max_output_bytes: Option<usize>,
+}
+
+/// Which of the `ResourceLimits` a program went over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    Steps,
+    RecursionDepth,
+    StringLength,
+    Time,
+    OutputBytes,
+}
+
+impl Display for Resource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        match self {
+            Self::Steps => write!(f, "step"),
+            Self::RecursionDepth => write!(f, "recursion depth"),
+            Self::StringLength => write!(f, "string length"),
+            Self::Time => write!(f, "time"),
+            Self::OutputBytes => write!(f, "output size"),
+        }
+    }
+}
@@ -1,2 +1,1630 @@
+mod builtins;
+#[allow(clippy::module_inception)]
 pub mod evaluator;
-mod types;
+pub mod limits;
+pub mod types;
+
+/// Tests for the evaluator. `evaluate_source` runs source code through the
+/// scanner, parser and evaluator, handing back the evaluator so its state can
+/// be inspected afterwards.
+#[cfg(test)]
+mod tests {
+    use super::evaluator::Evaluator;
+    use super::limits::{Resource, ResourceLimits};
+    use super::types::{ReefError, RuntimeError, RuntimeType, Scope};
+    use reef_core::{lex, parse};
+    use reef_syntax::ast::{Expr, Stmt};
+    use reef_syntax::common::{Boolean, ComparisonOperator};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    fn parse_source(source_code: &str) -> Vec<Stmt> {
+        let mut l = lex::Scanner::new(source_code, 0);
+        l.scan().expect("Failed to scan source code");
+
+        let mut p = parse::Parser::new(l.tokens, l.spans, 0);
+        p.parse_all().expect("Failed to parse source code");
+        p.program
+    }
+
+    fn evaluate_source(source_code: &str) -> Evaluator {
+        let mut e = Evaluator::new(parse_source(source_code), 0);
+        e.evaluate_program()
+            .expect("Failed to evaluate source code");
+        e
+    }
+
+    fn evaluate_source_error(source_code: &str) -> RuntimeError {
+        let mut e = Evaluator::new(parse_source(source_code), 0);
+        e.evaluate_program()
+            .expect_err("Expected evaluating the source code to fail")
+    }
+
+    fn get_variable(e: &Evaluator, name: &str) -> RuntimeType {
+        e.get_main_scope()
+            .get_variable(name)
+            .expect("Variable should exist")
+    }
+
+    #[test]
+    fn eval_str_shares_state_between_calls() {
+        let mut e = Evaluator::new(vec![], 0);
+
+        assert_eq!(e.eval_str("var x = 20;").unwrap(), RuntimeType::None);
+        assert_eq!(e.eval_str("x + 22;").unwrap(), RuntimeType::Integer(42));
+        assert!(matches!(
+            e.eval_str("var x = 1;"),
+            Err(ReefError::Runtime(_))
+        ));
+        assert!(matches!(e.eval_str("var = 1;"), Err(ReefError::Parser(_))));
+    }
+
+    #[test]
+    fn nil_is_a_value_unlike_a_statement_result() {
+        let mut e = Evaluator::new(vec![], 0);
+
+        assert_eq!(e.eval_str("nil;").unwrap(), RuntimeType::Nil);
+        assert_eq!(e.eval_str("var x = nil;").unwrap(), RuntimeType::None);
+        assert_eq!(e.eval_str("x;").unwrap(), RuntimeType::Nil);
+        assert_eq!(
+            e.eval_str("typeof nil;").unwrap(),
+            RuntimeType::String("nil".to_string())
+        );
+        assert_eq!(
+            e.eval_str("nil == nil;").unwrap(),
+            RuntimeType::Boolean(Boolean::True)
+        );
+
+        let buffer = SharedBuffer::default();
+        let mut e = Evaluator::new(parse_source("log nil;"), 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "nil\n");
+    }
+
+    #[test]
+    fn variables_snapshot_lists_every_binding() {
+        let mut e = Evaluator::new(vec![], 0);
+        e.eval_str("var name = \"reef\"; var count = 3; var done = false;")
+            .unwrap();
+
+        assert_eq!(
+            e.variables(),
+            vec![
+                ("count".to_string(), RuntimeType::Integer(3)),
+                ("done".to_string(), RuntimeType::Boolean(Boolean::False)),
+                ("name".to_string(), RuntimeType::String("reef".to_string())),
+            ]
+        );
+
+        // Taking the snapshot doesn't change anything.
+        assert_eq!(e.eval_str("count + 1;").unwrap(), RuntimeType::Integer(4));
+    }
+
+    #[test]
+    fn visible_variables_prefer_the_innermost_scope() {
+        let outer = Rc::new(RefCell::new(Scope::new(None)));
+        outer
+            .borrow_mut()
+            .set_variable("x", RuntimeType::Number(1.0))
+            .unwrap();
+        outer
+            .borrow_mut()
+            .set_variable("y", RuntimeType::Number(2.0))
+            .unwrap();
+
+        let mut inner = Scope::new(Some(Rc::clone(&outer)));
+        inner.set_variable("x", RuntimeType::Number(10.0)).unwrap();
+
+        assert_eq!(
+            inner.variables(),
+            vec![("x".to_string(), RuntimeType::Number(10.0))]
+        );
+        assert_eq!(
+            inner.visible_variables(),
+            vec![
+                ("x".to_string(), RuntimeType::Number(10.0)),
+                ("y".to_string(), RuntimeType::Number(2.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn declaration_without_value_is_nil() {
+        let e = evaluate_source("var x; var y = 5;");
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Nil);
+        assert_eq!(get_variable(&e, "y"), RuntimeType::Integer(5));
+
+        let e = evaluate_source("var x; x = \"set later\";");
+        assert_eq!(
+            get_variable(&e, "x"),
+            RuntimeType::String("set later".to_string())
+        );
+    }
+
+    #[test]
+    fn reassigning_a_typo_suggests_the_similar_variable() {
+        let err = evaluate_source_error("var total = 1; { totl = 2; }");
+        let message = err.to_string();
+
+        assert!(message.contains("var totl"), "{}", message);
+        assert!(message.contains("Did you mean \"total\"?"), "{}", message);
+
+        let message = evaluate_source_error("var total = 1; x = 2;").to_string();
+        assert!(!message.contains("Did you mean"), "{}", message);
+    }
+
+    #[test]
+    fn groups_are_full_expressions_on_either_side() {
+        let e = evaluate_source(
+            "var a = (1 + 2) * 3 == 9;
+            var b = 2 * (3 + 4) == 14;
+            var c = (1 + 2) * (3 + 4);",
+        );
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Integer(21));
+    }
+
+    #[test]
+    fn unary_minus_negates_any_operand() {
+        let e = evaluate_source(
+            "var x = 4;
+            var group = -(2 + 3);
+            var double = - - 5 == 5;
+            var ident = -x;
+            var sum = -5 + 3 == 0 - 2;
+            var product = -x * 2;",
+        );
+        assert_eq!(get_variable(&e, "group"), RuntimeType::Integer(-5));
+        assert_eq!(
+            get_variable(&e, "double"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(get_variable(&e, "ident"), RuntimeType::Integer(-4));
+        assert_eq!(get_variable(&e, "sum"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "product"), RuntimeType::Integer(-8));
+    }
+
+    #[test]
+    fn compares_identifiers_and_boolean_literals() {
+        let e = evaluate_source(
+            "var x = 3;
+            var flag = true;
+            var small = x < 5;
+            var set = flag == true;
+            var reversed = true == flag;",
+        );
+        for name in ["small", "set", "reversed"] {
+            assert_eq!(
+                get_variable(&e, name),
+                RuntimeType::Boolean(Boolean::True),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn modulo_takes_the_sign_of_the_divisor() {
+        let e = evaluate_source(
+            "var a = -1 % 3;
+            var b = 7 % 3;
+            var c = 7 % -3;
+            var d = -7 % -3;
+            var e = 6 % -3;
+            var f = 5.5 % 2;",
+        );
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Integer(2));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Integer(1));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Integer(-2));
+        assert_eq!(get_variable(&e, "d"), RuntimeType::Integer(-1));
+        assert_eq!(get_variable(&e, "e"), RuntimeType::Integer(0));
+        assert_eq!(get_variable(&e, "f"), RuntimeType::Number(1.5));
+    }
+
+    #[test]
+    fn integers_stay_integers_until_a_float_is_involved() {
+        let e = evaluate_source(
+            "var int_div = 5 / 2;
+            var float_div = 5.0 / 2;
+            var negative_div = -5 / 2;
+            var sum = 2 + 3;
+            var mixed = 2 + 0.5;
+            var overflow = 9223372036854775807 + 1;
+            var length = len([1, 2]);
+            var same = 5 == 5.0;",
+        );
+
+        assert!(matches!(
+            get_variable(&e, "int_div"),
+            RuntimeType::Integer(2)
+        ));
+        assert!(matches!(get_variable(&e, "float_div"), RuntimeType::Number(n) if n == 2.5));
+        assert!(matches!(
+            get_variable(&e, "negative_div"),
+            RuntimeType::Integer(-3)
+        ));
+        assert!(matches!(get_variable(&e, "sum"), RuntimeType::Integer(5)));
+        assert!(matches!(get_variable(&e, "mixed"), RuntimeType::Number(n) if n == 2.5));
+        assert!(matches!(
+            get_variable(&e, "overflow"),
+            RuntimeType::Number(_)
+        ));
+        assert!(matches!(
+            get_variable(&e, "length"),
+            RuntimeType::Integer(2)
+        ));
+        assert_eq!(
+            get_variable(&e, "same"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(get_variable(&e, "int_div").type_name(), "number");
+        assert_eq!(get_variable(&e, "float_div").to_string(), "2.5");
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        let e = evaluate_source(
+            "var arr = [5];
+            fun two() { return 2; }
+            var product_first = 1 + 2 * 3;
+            var left_to_right = 10 - 2 - 3;
+            var negated = -1 + 2;
+            var indexed = arr[0] + 2;
+            var called = two() * 3;
+            var logical = 1 < 2 and 3 > 4 or not false;",
+        );
+        assert_eq!(get_variable(&e, "product_first"), RuntimeType::Integer(7));
+        assert_eq!(get_variable(&e, "left_to_right"), RuntimeType::Integer(5));
+        assert_eq!(get_variable(&e, "negated"), RuntimeType::Integer(1));
+        assert_eq!(get_variable(&e, "indexed"), RuntimeType::Integer(7));
+        assert_eq!(get_variable(&e, "called"), RuntimeType::Integer(6));
+        assert_eq!(
+            get_variable(&e, "logical"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+    }
+
+    #[test]
+    fn len_counts_characters_and_elements() {
+        let e = evaluate_source(
+            "var string_length = len(\"hello\") == 5;
+            var array_length = len([1, 2, 3]) == 3;
+            var unicode_length = len(\"héllo\");",
+        );
+        assert_eq!(
+            get_variable(&e, "string_length"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            get_variable(&e, "array_length"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(get_variable(&e, "unicode_length"), RuntimeType::Integer(5));
+    }
+
+    #[test]
+    fn structs_are_built_and_their_fields_read() {
+        let e = evaluate_source(
+            "struct Point { x, y }
+            struct Line { start, end, }
+            var p = Point { x = 1, y = 2 };
+            var sum = p.x + p.y;
+            var partial = Point { y = 5 }.x;
+            var line = Line { start = p, end = Point { y = 4, x = 3 } };
+            var end_x = line.end.x;
+            var shown = str(p);
+            var kind = typeof p;",
+        );
+
+        assert_eq!(get_variable(&e, "sum"), RuntimeType::Integer(3));
+        assert_eq!(get_variable(&e, "partial"), RuntimeType::Nil);
+        assert_eq!(get_variable(&e, "end_x"), RuntimeType::Integer(3));
+        assert_eq!(
+            get_variable(&e, "shown"),
+            RuntimeType::String("Point { x: 1, y: 2 }".to_string())
+        );
+        assert_eq!(
+            get_variable(&e, "kind"),
+            RuntimeType::String("struct".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_struct_fields_are_errors() {
+        let declare = "struct Point { x, y } var p = Point { x = 1, y = 2 };";
+
+        for (source_code, expected) in [
+            ("var z = p.z;", "no field called \"z\""),
+            ("var q = Point { z = 1 };", "no field called \"z\""),
+            ("var q = Size { w = 1 };", "no struct called Size"),
+            ("var n = 5; var x = n.x;", "only of a struct"),
+            ("struct Point { a }", "already been declared"),
+        ] {
+            match evaluate_source_error(&format!("{} {}", declare, source_code)) {
+                RuntimeError::Generic(message) => {
+                    assert!(message.contains(expected), "{}: {}", source_code, message)
+                }
+                err => panic!("Expected an error for {}, got {:?}", source_code, err),
+            }
+        }
+    }
+
+    #[test]
+    fn str_and_num_convert_between_types() {
+        let e = evaluate_source(
+            "var a = str(12.5);
+            var b = str(nil);
+            var c = str([1, true]);
+            var d = num(\"  12 \");
+            var f = num(\"-0.5\") + num(3);
+            var round_trip = num(str(42)) == 42;",
+        );
+        let string = |s: &str| RuntimeType::String(s.to_string());
+
+        assert_eq!(get_variable(&e, "a"), string("12.5"));
+        assert_eq!(get_variable(&e, "b"), string("nil"));
+        assert_eq!(get_variable(&e, "c"), string("[1, true]"));
+        assert_eq!(get_variable(&e, "d"), RuntimeType::Integer(12));
+        assert_eq!(get_variable(&e, "f"), RuntimeType::Number(2.5));
+        assert_eq!(
+            get_variable(&e, "round_trip"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+    }
+
+    #[test]
+    fn num_rejects_text_that_is_not_a_number() {
+        for source_code in ["num(\"abc\");", "num(\"\");", "num(\"inf\");", "num(true);"] {
+            match evaluate_source_error(source_code) {
+                RuntimeError::Generic(message) => assert!(message.starts_with("num()")),
+                err => panic!("Expected a num() error for {}, got {:?}", source_code, err),
+            }
+        }
+    }
+
+    #[test]
+    fn passing_assertions_do_nothing() {
+        let e =
+            evaluate_source("assert(1 + 1 == 2); assert(\"truthy\", \"unused\"); var after = 1;");
+        assert_eq!(get_variable(&e, "after"), RuntimeType::Integer(1));
+    }
+
+    #[test]
+    fn failing_assertions_stop_the_program() {
+        assert_eq!(
+            evaluate_source_error("assert(1 > 2);"),
+            RuntimeError::AssertionFailed(None)
+        );
+        assert_eq!(
+            evaluate_source_error("var x = nil; assert(x, \"x should be set\");"),
+            RuntimeError::AssertionFailed(Some("x should be set".to_string()))
+        );
+        evaluate_source_error("assert();");
+    }
+
+    #[test]
+    fn only_numbers_can_be_ordered() {
+        let err = evaluate_source_error("var x = true < false;");
+        assert_eq!(
+            err,
+            RuntimeError::TypeMismatch {
+                operator: ComparisonOperator::LessThan,
+                lhs: "boolean",
+                rhs: "boolean",
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Cannot use `<` on a boolean and a boolean, only numbers can be ordered"
+        );
+
+        assert_eq!(
+            evaluate_source_error("var x = nil > 1;").to_string(),
+            "Cannot use `>` on a nil and a number, only numbers can be ordered"
+        );
+    }
+
+    #[test]
+    fn bitwise_operators_work_on_whole_numbers() {
+        let mut e = Evaluator::new(vec![], 0);
+
+        for (source_code, expected) in [
+            ("6 & 3 == 2;", RuntimeType::Boolean(Boolean::True)),
+            ("1 << 4 == 16;", RuntimeType::Boolean(Boolean::True)),
+            ("6 | 3;", RuntimeType::Integer(7)),
+            ("6 ^ 3;", RuntimeType::Integer(5)),
+            ("-16 >> 2;", RuntimeType::Integer(-4)),
+            // Shifts bind looser than `+`, and `&` looser than shifts.
+            ("1 << 1 + 1;", RuntimeType::Integer(4)),
+            ("5 & 1 << 2;", RuntimeType::Integer(4)),
+        ] {
+            assert_eq!(
+                e.eval_str(source_code).unwrap(),
+                expected,
+                "{}",
+                source_code
+            );
+        }
+
+        for (source_code, expected) in [
+            ("var x = 1.5 & 1;", "only work on whole numbers"),
+            ("var x = 1 << 64;", "only by 0 to 63"),
+            ("var x = 1 >> -1;", "only by 0 to 63"),
+        ] {
+            match evaluate_source_error(source_code) {
+                RuntimeError::Generic(message) => {
+                    assert!(message.contains(expected), "{}: {}", source_code, message)
+                }
+                err => panic!("Expected an error for {}, got {:?}", source_code, err),
+            }
+        }
+    }
+
+    #[test]
+    fn multiplying_a_string_repeats_it() {
+        let mut e = Evaluator::new(vec![], 0);
+
+        assert_eq!(
+            e.eval_str("\"x\" * 3 == \"xxx\";").unwrap(),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            e.eval_str("2 * \"ab\";").unwrap(),
+            RuntimeType::String("abab".to_string())
+        );
+        assert_eq!(
+            e.eval_str("\"ab\" * 0;").unwrap(),
+            RuntimeType::String("".to_string())
+        );
+
+        for source_code in ["var s = \"x\" * -1;", "var s = \"x\" * 1.5;"] {
+            match evaluate_source_error(source_code) {
+                RuntimeError::Generic(message) => {
+                    assert!(message.contains("whole number of times"), "{}", message)
+                }
+                err => panic!("Expected an error for {}, got {:?}", source_code, err),
+            }
+        }
+
+        assert_eq!(
+            evaluate_source_error("var s = \"ab\" * 100000000000000000000;"),
+            RuntimeError::ResourceExhausted(Resource::StringLength)
+        );
+        assert_eq!(
+            evaluate_source_error("var s = \"ab\" * 9223372036854775807;"),
+            RuntimeError::ResourceExhausted(Resource::StringLength)
+        );
+    }
+
+    #[test]
+    fn len_of_other_types_errors() {
+        for source_code in ["len(1);", "len(true);", "len(nil);"] {
+            assert!(matches!(
+                evaluate_source_error(source_code),
+                RuntimeError::Generic(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn strings_can_be_indexed_by_character() {
+        let e = evaluate_source(
+            "var s = \"héllo\";
+            var second = \"hello\"[1] == \"e\";
+            var accented = s[1];",
+        );
+        assert_eq!(
+            get_variable(&e, "second"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            get_variable(&e, "accented"),
+            RuntimeType::String("é".to_string())
+        );
+
+        assert_eq!(
+            evaluate_source_error("var s = \"abc\"; var c = s[3];"),
+            RuntimeError::IndexOutOfBounds {
+                index: 3,
+                length: 3
+            }
+        );
+    }
+
+    #[test]
+    fn format_rounds_to_a_number_of_digits() {
+        let e = evaluate_source(
+            "var third = format(1.0 / 3, 2); var whole = format(2, 0); var padded = format(0.5, 3);",
+        );
+
+        assert_eq!(
+            get_variable(&e, "third"),
+            RuntimeType::String("0.33".to_string())
+        );
+        assert_eq!(
+            get_variable(&e, "whole"),
+            RuntimeType::String("2".to_string())
+        );
+        assert_eq!(
+            get_variable(&e, "padded"),
+            RuntimeType::String("0.500".to_string())
+        );
+        assert_eq!(
+            get_variable(
+                &evaluate_source("var b = format(1.0 / 3, 2) == \"0.33\";"),
+                "b"
+            ),
+            RuntimeType::Boolean(Boolean::True)
+        );
+
+        for source_code in [
+            "format(1, 0 - 1);",
+            "format(1, 1.5);",
+            "format(\"1\", 2);",
+            "format(1, 101);",
+            "format(1, 100000000000);",
+        ] {
+            assert!(
+                matches!(evaluate_source_error(source_code), RuntimeError::Generic(_)),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn string_builtins_can_be_chained() {
+        let e = evaluate_source(
+            "var s = \"  Reef Lang \";
+            var shout = upper(trim(s));
+            var quiet = lower(trim(s));
+            var words = split(lower(trim(s)), \" \");
+            var csv = split(\"a,,b\", \",\");
+            var chars = split(\"ab\", \"\");",
+        );
+
+        assert_eq!(
+            get_variable(&e, "shout"),
+            RuntimeType::String("REEF LANG".to_string())
+        );
+        assert_eq!(
+            get_variable(&e, "quiet"),
+            RuntimeType::String("reef lang".to_string())
+        );
+        assert_eq!(get_variable(&e, "words").to_string(), "[reef, lang]");
+        assert_eq!(get_variable(&e, "csv").to_string(), "[a, , b]");
+        assert_eq!(get_variable(&e, "chars").to_string(), "[a, b]");
+    }
+
+    #[test]
+    fn string_builtins_reject_other_types() {
+        for (source_code, name, type_name) in [
+            ("upper(1);", "upper", "number"),
+            ("lower([\"a\"]);", "lower", "array"),
+            ("trim(nil);", "trim", "nil"),
+            ("split(\"a\", 1);", "split", "number"),
+        ] {
+            assert_eq!(
+                evaluate_source_error(source_code),
+                RuntimeError::Generic(format!("{}() expects a string, got a {}", name, type_name)),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn substr_takes_a_range_of_characters() {
+        let e = evaluate_source(
+            "var middle = substr(\"hello\", 1, 4);
+            var empty = substr(\"hello\", 2, 2);",
+        );
+        assert_eq!(
+            get_variable(&e, "middle"),
+            RuntimeType::String("ell".to_string())
+        );
+        assert_eq!(
+            get_variable(&e, "empty"),
+            RuntimeType::String(String::new())
+        );
+
+        assert_eq!(
+            evaluate_source_error("substr(\"hello\", 0, 6);"),
+            RuntimeError::IndexOutOfBounds {
+                index: 6,
+                length: 5
+            }
+        );
+        evaluate_source_error("substr(\"hello\", 3, 1);");
+    }
+
+    #[test]
+    fn return_stops_block() {
+        let e = evaluate_source("var x = 1; { x = 2; return; x = 3; } x = 4;");
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn return_stops_program() {
+        let e = evaluate_source("var x = 1; return x; x = 2;");
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(1));
+    }
+
+    #[test]
+    fn return_inside_if_stops_block() {
+        let e = evaluate_source("var x = 1; { if (1 == 1) then { return 5; } x = 2; }");
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(1));
+    }
+
+    #[test]
+    fn boolean_arithmetic_errors_by_default() {
+        evaluate_source_error("var x = true + 1;");
+    }
+
+    #[test]
+    fn boolean_arithmetic_coerces_when_permissive() {
+        let mut e = Evaluator::new(parse_source("var x = true + 1; var y = 5 * false;"), 0);
+        e.set_permissive(true);
+        e.evaluate_program().unwrap();
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(2));
+        assert_eq!(get_variable(&e, "y"), RuntimeType::Integer(0));
+    }
+
+    #[test]
+    fn builtins_lists_registered_functions() {
+        let e = evaluate_source("var names = builtins();");
+
+        match get_variable(&e, "names") {
+            RuntimeType::Array(names) => {
+                assert!(names.contains(&RuntimeType::String("builtins".to_string())));
+                assert!(names.contains(&RuntimeType::String("len".to_string())));
+            }
+            v => panic!("Expected builtins() to return an array, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn calling_unknown_function_errors() {
+        evaluate_source_error("missing();");
+    }
+
+    #[test]
+    fn division_by_zero_errors() {
+        assert_eq!(
+            evaluate_source_error("var x = 10 / 0;"),
+            RuntimeError::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn modulo_by_zero_errors() {
+        assert_eq!(
+            evaluate_source_error("var x = 10 % 0;"),
+            RuntimeError::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn numbers_display_without_float_noise() {
+        let e = evaluate_source(
+            "var a = 0.1 + 0.2; var b = 1.0 / 3; var c = 6 / 2; var d = 2.5; var e = 0 - 0.00000000001;",
+        );
+
+        assert_eq!(get_variable(&e, "a").to_string(), "0.3");
+        assert_eq!(get_variable(&e, "b").to_string(), "0.3333333333");
+        assert_eq!(get_variable(&e, "c").to_string(), "3");
+        assert_eq!(get_variable(&e, "d").to_string(), "2.5");
+        assert_eq!(get_variable(&e, "e").to_string(), "0");
+    }
+
+    #[test]
+    fn indexes_arrays() {
+        let e = evaluate_source("var a = [10, 20, 30]; var b = a[1]; var c = [1, 2][0];");
+
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Integer(20));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Integer(1));
+    }
+
+    #[test]
+    fn indexes_nested_arrays() {
+        let e = evaluate_source("var a = [[1, 2], [3, [4, 5]], []]; var b = a[1][1][0];");
+
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Integer(4));
+        assert_eq!(
+            get_variable(&e, "a").to_string(),
+            "[[1, 2], [3, [4, 5]], []]"
+        );
+    }
+
+    #[test]
+    fn negative_indexes_count_from_the_end() {
+        let e = evaluate_source(
+            "var a = [10, 20, 30]; var last = a[-1]; var first = a[-3]; var c = \"reef\"[-2];",
+        );
+
+        assert_eq!(get_variable(&e, "last"), RuntimeType::Integer(30));
+        assert_eq!(get_variable(&e, "first"), RuntimeType::Integer(10));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::String("e".to_string()));
+        assert_eq!(
+            evaluate_source_error("var a = [1, 2, 3]; var b = a[-4];"),
+            RuntimeError::IndexOutOfBounds {
+                index: -4,
+                length: 3
+            }
+        );
+    }
+
+    #[test]
+    fn slices_arrays_and_strings() {
+        let e = evaluate_source(
+            "var a = [1, 2, 3, 4];
+            var middle = a[1:3];
+            var head = a[:2];
+            var tail = a[-2:];
+            var all = a[:];
+            var none = a[4:];
+            var s = \"hello\"[1:-1];",
+        );
+
+        assert_eq!(get_variable(&e, "middle").to_string(), "[2, 3]");
+        assert_eq!(get_variable(&e, "head").to_string(), "[1, 2]");
+        assert_eq!(get_variable(&e, "tail").to_string(), "[3, 4]");
+        assert_eq!(get_variable(&e, "all"), get_variable(&e, "a"));
+        assert_eq!(get_variable(&e, "none").to_string(), "[]");
+        assert_eq!(
+            get_variable(&e, "s"),
+            RuntimeType::String("ell".to_string())
+        );
+
+        assert_eq!(
+            evaluate_source_error("var a = [1, 2][0:3];"),
+            RuntimeError::IndexOutOfBounds {
+                index: 3,
+                length: 2
+            }
+        );
+        assert!(matches!(
+            evaluate_source_error("var a = [1, 2, 3][2:1];"),
+            RuntimeError::Generic(_)
+        ));
+    }
+
+    #[test]
+    fn indexing_out_of_bounds_errors() {
+        assert_eq!(
+            evaluate_source_error("var a = [1, 2]; var b = a[2];"),
+            RuntimeError::IndexOutOfBounds {
+                index: 2,
+                length: 2
+            }
+        );
+    }
+
+    #[test]
+    fn builds_and_indexes_maps() {
+        let e = evaluate_source(
+            "var k = \"b\"; var m = { \"a\" = 1, k = [2], \"a\" = 3 }; var a = m[\"a\"]; var b = m[k][0]; var n = len(m);",
+        );
+
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Integer(3));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Integer(2));
+        assert_eq!(get_variable(&e, "n"), RuntimeType::Integer(2));
+        assert_eq!(get_variable(&e, "m").to_string(), "{ a = 3, b = [2] }");
+        assert_eq!(get_variable(&e, "m").type_name(), "map");
+    }
+
+    #[test]
+    fn looking_up_a_missing_key_errors() {
+        assert_eq!(
+            evaluate_source_error("var m = { \"a\" = 1 }; var b = m[\"b\"];"),
+            RuntimeError::MissingKey("b".to_string())
+        );
+        assert!(matches!(
+            evaluate_source_error("var m = { \"a\" = 1 }; var b = m[0];"),
+            RuntimeError::Generic(_)
+        ));
+        assert!(matches!(
+            evaluate_source_error("var m = { 1 = 1 };"),
+            RuntimeError::Generic(_)
+        ));
+    }
+
+    #[test]
+    fn arrays_compare_structurally() {
+        let e = evaluate_source("var a = [1, 2]; var b = [1, 2]; var c = [1, 3]; var d = [1];");
+
+        assert_eq!(get_variable(&e, "a"), get_variable(&e, "b"));
+        assert_ne!(get_variable(&e, "a"), get_variable(&e, "c"));
+        assert_ne!(get_variable(&e, "a"), get_variable(&e, "d"));
+    }
+
+    #[test]
+    fn equality_operators_compare_arrays_and_maps_structurally() {
+        let e = evaluate_source(
+            "var same = [1, 2] == [1, 2];
+            var different = [1, 2] == [1, 3];
+            var not_different = [1, 2] != [1, 3];
+            var reordered = { \"a\" = 1, \"b\" = 2 } == { \"b\" = 2, \"a\" = 1 };
+            var missing_key = { \"a\" = 1 } == { \"a\" = 1, \"b\" = 2 };",
+        );
+
+        assert_eq!(
+            get_variable(&e, "same"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            get_variable(&e, "different"),
+            RuntimeType::Boolean(Boolean::False)
+        );
+        assert_eq!(
+            get_variable(&e, "not_different"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            get_variable(&e, "reordered"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(
+            get_variable(&e, "missing_key"),
+            RuntimeType::Boolean(Boolean::False)
+        );
+    }
+
+    #[test]
+    fn scalars_compare_by_value() {
+        let num = |n: f64| RuntimeType::Number(n);
+        let string = |s: &str| RuntimeType::String(s.to_string());
+
+        assert_eq!(num(1.0), num(1.0));
+        assert_ne!(num(1.0), num(2.0));
+        assert_eq!(string("a"), string("a"));
+        assert_ne!(string("a"), string("b"));
+        assert_eq!(
+            RuntimeType::Boolean(Boolean::True),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_ne!(
+            RuntimeType::Boolean(Boolean::True),
+            RuntimeType::Boolean(Boolean::False)
+        );
+        assert_eq!(RuntimeType::None, RuntimeType::None);
+
+        assert_ne!(num(1.0), string("1"));
+        assert_ne!(num(1.0), RuntimeType::Boolean(Boolean::True));
+        assert_ne!(num(0.0), RuntimeType::None);
+    }
+
+    #[test]
+    fn equality_operators_compare_numbers() {
+        let e = evaluate_source("var a = 1 == 1; var b = 1 != 1; var c = 1 == \"1\";");
+
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Boolean(Boolean::False));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Boolean(Boolean::False));
+    }
+
+    #[test]
+    fn loop_body_declarations_are_fresh_each_iteration() {
+        let e = evaluate_source(
+            "var i = 0;
+            var total = 0;
+            var matches = 0;
+            for (i < 3) do {
+                var temp = i * 2;
+                if (temp == i * 2) then {
+                    matches = matches + 1;
+                }
+                total = total + temp;
+                i = i + 1;
+            }",
+        );
+
+        assert_eq!(get_variable(&e, "i"), RuntimeType::Integer(3));
+        assert_eq!(get_variable(&e, "total"), RuntimeType::Integer(6));
+        assert_eq!(get_variable(&e, "matches"), RuntimeType::Integer(3));
+        assert!(e.get_main_scope().get_variable("temp").is_err());
+    }
+
+    #[test]
+    fn return_stops_loop() {
+        let e =
+            evaluate_source("var i = 0; { for (true) do { i = i + 1; return i; } } var after = 1;");
+
+        assert_eq!(get_variable(&e, "i"), RuntimeType::Integer(1));
+        assert!(e.get_main_scope().get_variable("after").is_err());
+    }
+
+    #[test]
+    fn not_negates_booleans() {
+        let e = evaluate_source(
+            "var a = not true; var b = not false; var c = not not true; var d = not (1 < 2);",
+        );
+
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Boolean(Boolean::False));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "d"), RuntimeType::Boolean(Boolean::False));
+    }
+
+    #[test]
+    fn not_uses_truthiness() {
+        let e = evaluate_source("var a = not 1; var b = not nil; var c = not \"\";");
+
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Boolean(Boolean::False));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(get_variable(&e, "c"), RuntimeType::Boolean(Boolean::False));
+    }
+
+    #[test]
+    fn calls_declared_functions() {
+        let e = evaluate_source("fun add(a, b) { return a + b; } var x = add(5, 10);");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(15));
+    }
+
+    #[test]
+    fn functions_without_return_give_nil() {
+        let e = evaluate_source("fun nothing() { var a = 1; } var x = nothing();");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Nil);
+    }
+
+    #[test]
+    fn functions_see_their_declaring_scope() {
+        let e = evaluate_source(
+            "var count = 0;
+            fun bump() { count = count + 1; }
+            bump();
+            bump();",
+        );
+
+        assert_eq!(get_variable(&e, "count"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn calling_with_wrong_argument_count_errors() {
+        assert!(matches!(
+            evaluate_source_error("fun add(a, b) { return a + b; } add(1);"),
+            RuntimeError::Generic(_)
+        ));
+    }
+
+    /// Declares `x` as `lhs <operator> missing`, where `missing` is a variable
+    /// that doesn't exist and errors if it is ever evaluated.
+    fn evaluate_logical_with_missing_rhs(
+        lhs: Boolean,
+        operator: ComparisonOperator,
+    ) -> Result<RuntimeType, RuntimeError> {
+        let program = vec![Stmt::VariableDeclaration {
+            name: "x".to_string(),
+            value: Some(Expr::ComparisonExpression {
+                lhs: Box::new(Expr::Boolean(lhs)),
+                rhs: Box::new(Expr::Identifier("missing".to_string())),
+                operator,
+            }),
+        }];
+
+        let mut e = Evaluator::new(program, 0);
+        e.evaluate_program()?;
+
+        let x = e.get_main_scope().get_variable("x");
+        x
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        assert_eq!(
+            evaluate_logical_with_missing_rhs(Boolean::False, ComparisonOperator::And),
+            Ok(RuntimeType::Boolean(Boolean::False))
+        );
+        assert_eq!(
+            evaluate_logical_with_missing_rhs(Boolean::True, ComparisonOperator::Or),
+            Ok(RuntimeType::Boolean(Boolean::True))
+        );
+    }
+
+    #[test]
+    fn and_or_evaluate_rhs_when_needed() {
+        assert!(evaluate_logical_with_missing_rhs(Boolean::True, ComparisonOperator::And).is_err());
+        assert!(evaluate_logical_with_missing_rhs(Boolean::False, ComparisonOperator::Or).is_err());
+    }
+
+    fn limit_hit_by(source_code: &str, limits: ResourceLimits) -> RuntimeError {
+        let mut e = Evaluator::new_with_limits(parse_source(source_code), 0, limits);
+        e.evaluate_program()
+            .expect_err("Expected a resource limit to be hit")
+    }
+
+    #[test]
+    fn step_limit_stops_infinite_loops() {
+        let limits = ResourceLimits {
+            max_steps: Some(100),
+            ..Default::default()
+        };
+        let mut e =
+            Evaluator::new_with_limits(parse_source("var a = 1; for (true) do { }"), 0, limits);
+
+        assert_eq!(
+            e.evaluate_program(),
+            Err(RuntimeError::ResourceExhausted(Resource::Steps))
+        );
+        // Whatever ran before the limit was hit is kept.
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Integer(1));
+    }
+
+    #[test]
+    fn recursion_limit_stops_runaway_recursion() {
+        let limits = ResourceLimits {
+            max_recursion_depth: Some(50),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            limit_hit_by("fun f() { return f(); } f();", limits),
+            RuntimeError::ResourceExhausted(Resource::RecursionDepth)
+        );
+    }
+
+    #[test]
+    fn string_length_limit_rejects_long_strings() {
+        let limits = ResourceLimits {
+            max_string_length: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            limit_hit_by("var s = \"hello\";", limits),
+            RuntimeError::ResourceExhausted(Resource::StringLength)
+        );
+    }
+
+    #[test]
+    fn string_length_limit_applies_to_repeated_strings() {
+        let limits = ResourceLimits {
+            max_string_length: Some(5),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            limit_hit_by("var s = \"ab\" * 1000000000000;", limits),
+            RuntimeError::ResourceExhausted(Resource::StringLength)
+        );
+    }
+
+    #[test]
+    fn timeout_stops_long_running_programs() {
+        let limits = ResourceLimits {
+            timeout: Some(std::time::Duration::from_millis(10)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            limit_hit_by("for (true) do { }", limits),
+            RuntimeError::ResourceExhausted(Resource::Time)
+        );
+    }
+
+    #[test]
+    fn output_limit_stops_runaway_logging() {
+        let buffer = SharedBuffer::default();
+        let limits = ResourceLimits {
+            max_output_bytes: Some(20),
+            ..Default::default()
+        };
+
+        let mut e =
+            Evaluator::new_with_limits(parse_source("for (true) do { log \"spam\"; }"), 0, limits);
+        e.set_output(Box::new(buffer.clone()));
+
+        assert_eq!(
+            e.evaluate_program(),
+            Err(RuntimeError::ResourceExhausted(Resource::OutputBytes))
+        );
+        assert_eq!(
+            String::from_utf8(buffer.0.take()).unwrap(),
+            "spam\n".repeat(4)
+        );
+    }
+
+    #[test]
+    fn break_stops_the_loop() {
+        let e = evaluate_source(
+            "var i = 0;
+            for (true) do {
+                i = i + 1;
+                if (i == 3) then { break; }
+            }",
+        );
+        assert_eq!(get_variable(&e, "i"), RuntimeType::Integer(3));
+    }
+
+    #[test]
+    fn break_only_stops_the_innermost_loop() {
+        let e = evaluate_source(
+            "var outer = 0;
+            var inner = 0;
+            for (outer < 2) do {
+                outer = outer + 1;
+                for (true) do { inner = inner + 1; break; }
+            }",
+        );
+        assert_eq!(get_variable(&e, "outer"), RuntimeType::Integer(2));
+        assert_eq!(get_variable(&e, "inner"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn continue_skips_to_the_next_iteration() {
+        let buffer = SharedBuffer::default();
+        let program = parse_source(
+            "var i = 0;
+            for (i < 10) do {
+                i = i + 1;
+                if (0 == i % 2) then { continue; }
+                log i;
+            }",
+        );
+
+        let mut e = Evaluator::new(program, 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.0.take()).unwrap(),
+            "1\n3\n5\n7\n9\n"
+        );
+    }
+
+    #[test]
+    fn c_style_for_loop_sums_to_45() {
+        let buffer = SharedBuffer::default();
+        let program = parse_source(
+            "var sum = 0;
+            for (var i = 0; i < 10; i = i + 1) do {
+                sum = sum + i;
+            }
+            log sum;",
+        );
+
+        let mut e = Evaluator::new(program, 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "45\n");
+        // The loop variable only exists inside the loop.
+        assert!(e.get_main_scope().get_variable("i").is_err());
+    }
+
+    #[test]
+    fn c_style_for_loop_runs_the_step_after_continue() {
+        let e = evaluate_source(
+            "var odd = 0;
+            for (var i = 0; i < 10; i = i + 1) do {
+                if (0 == i % 2) then { continue; }
+                odd = odd + 1;
+            }
+            var j = 0;
+            for (; j < 3;) do { j = j + 1; }",
+        );
+        assert_eq!(get_variable(&e, "odd"), RuntimeType::Integer(5));
+        assert_eq!(get_variable(&e, "j"), RuntimeType::Integer(3));
+    }
+
+    #[test]
+    fn loop_control_outside_loop_errors_when_constructed() {
+        let mut e = Evaluator::new(vec![Stmt::Break], 0);
+        assert!(matches!(
+            e.evaluate_program(),
+            Err(RuntimeError::Generic(_))
+        ));
+    }
+
+    #[test]
+    fn recursive_functions_can_call_themselves() {
+        let e = evaluate_source(
+            "fun fact(n) { if (n <= 1) then { return 1; } return n * fact(n - 1); }
+            var result = fact(5);",
+        );
+        assert_eq!(get_variable(&e, "result"), RuntimeType::Integer(120));
+    }
+
+    /// A function declared inside another function closes over the outer
+    /// function's scope, which is also where its own name is declared.
+    #[test]
+    fn nested_functions_can_call_themselves() {
+        let e = evaluate_source(
+            "fun outer() {
+                fun count(n) { if (n == 0) then { return 0; } return 1 + count(n - 1); }
+                return count(4);
+            }
+            var result = outer();",
+        );
+        assert_eq!(get_variable(&e, "result"), RuntimeType::Integer(4));
+    }
+
+    #[test]
+    fn top_level_functions_are_hoisted() {
+        let e = evaluate_source(
+            "fun main() { return 1 + helper(); }
+            var result = main();
+            fun helper() { return 41; }",
+        );
+        assert_eq!(get_variable(&e, "result"), RuntimeType::Integer(42));
+    }
+
+    #[test]
+    fn constants_cannot_be_reassigned() {
+        let mut e = Evaluator::new(vec![], 0);
+        e.eval_str("const MAX = 10;").unwrap();
+        assert_eq!(e.eval_str("MAX * 2;").unwrap(), RuntimeType::Integer(20));
+
+        let err = evaluate_source_error("const MAX = 10; MAX = 4;");
+        assert_eq!(err, RuntimeError::AssignToConst("MAX".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "Cannot reassign \"MAX\" because it was declared with `const`"
+        );
+
+        // Including from inside a block, but a new variable can shadow it.
+        evaluate_source_error("const MAX = 10; { MAX = 4; }");
+        let mut e = Evaluator::new(vec![], 0);
+        e.eval_str("const MAX = 10; { var MAX = 1; MAX = 4; }")
+            .unwrap();
+        assert_eq!(e.eval_str("MAX;").unwrap(), RuntimeType::Integer(10));
+    }
+
+    #[test]
+    fn only_function_declarations_are_hoisted() {
+        evaluate_source_error("var y = x; var x = 1;");
+        evaluate_source_error("{ f(); fun f() { } }");
+    }
+
+    #[test]
+    fn evaluates_constructed_block() {
+        let program = vec![
+            Stmt::VariableDeclaration {
+                name: "x".to_string(),
+                value: Some(Expr::NumberLiteral(1.0)),
+            },
+            Stmt::BlockStatement(vec![
+                Stmt::EmptyStatement,
+                Stmt::VariableReassignment {
+                    name: "x".to_string(),
+                    value: Expr::NumberLiteral(2.0),
+                },
+            ]),
+        ];
+
+        let mut e = Evaluator::new(program, 0);
+        e.evaluate_program().expect("Failed to evaluate block");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Number(2.0));
+    }
+
+    #[test]
+    fn every_statement_kind_parses_and_evaluates() {
+        let program = parse_source(
+            "var x = 1;
+            x = 2;
+            log x;
+            x;
+            { x = 3; }
+            if (true) then { x = 4; }
+            for (x < 5) do { x = x + 1; }
+            for (true) do { break; }
+            fun get() { return x; }
+            var y = get();
+            { return; }
+            x = 100;",
+        );
+
+        let has = |kind: fn(&Stmt) -> bool| program.iter().any(kind);
+        assert!(has(|s| matches!(s, Stmt::EmptyStatement)));
+        assert!(has(|s| matches!(s, Stmt::BlockStatement(_))));
+        assert!(has(|s| matches!(s, Stmt::ExpressionStatement(_))));
+        assert!(has(|s| matches!(s, Stmt::LogStatement(_))));
+        assert!(has(|s| matches!(s, Stmt::IfStatement { .. })));
+        assert!(has(|s| matches!(s, Stmt::ForLoop { .. })));
+        assert!(has(|s| matches!(s, Stmt::VariableDeclaration { .. })));
+        assert!(has(|s| matches!(s, Stmt::VariableReassignment { .. })));
+        assert!(has(|s| matches!(s, Stmt::FunctionDeclaration { .. })));
+        assert!(has(
+            |s| matches!(s, Stmt::ForLoop { body, .. } if matches!(&**body, Stmt::BlockStatement(b) if matches!(b[0], Stmt::Break)))
+        ));
+        assert!(has(
+            |s| matches!(s, Stmt::BlockStatement(b) if matches!(b[0], Stmt::ReturnStatement(_)))
+        ));
+
+        let mut e = Evaluator::new(program, 0);
+        e.evaluate_program()
+            .expect("Failed to evaluate every statement kind");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(5));
+        assert_eq!(get_variable(&e, "y"), RuntimeType::Integer(5));
+    }
+
+    #[test]
+    fn reassigning_undeclared_variable_errors() {
+        assert!(matches!(
+            evaluate_source_error("x = 5;"),
+            RuntimeError::Generic(_)
+        ));
+    }
+
+    #[test]
+    fn reassigning_declared_variable_works() {
+        let e = evaluate_source("var x = 1; x = 5;");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(5));
+    }
+
+    #[test]
+    fn redeclaring_variable_errors() {
+        assert!(matches!(
+            evaluate_source_error("var x = 1; var x = 5;"),
+            RuntimeError::Generic(_)
+        ));
+    }
+
+    #[test]
+    fn block_declarations_do_not_leak() {
+        let e = evaluate_source("{ var inner = 1; } if (true) then { var also_inner = 2; }");
+
+        assert!(e.get_main_scope().get_variable("inner").is_err());
+        assert!(e.get_main_scope().get_variable("also_inner").is_err());
+    }
+
+    #[test]
+    fn blocks_shadow_outer_variables() {
+        let e = evaluate_source(
+            "var x = 1;
+            var seen = 0;
+            {
+                var x = 2;
+                seen = x;
+            }",
+        );
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(1));
+        assert_eq!(get_variable(&e, "seen"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn blocks_read_and_reassign_outer_variables() {
+        let e = evaluate_source("var x = 1; { { x = x + 1; } }");
+
+        assert_eq!(get_variable(&e, "x"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn equality_works_across_every_type() {
+        let e = evaluate_source(
+            "var a = \"a\" == \"a\";
+            var b = 1 == 1;
+            var c = true != false;
+            var d = nil == nil;
+            var f = \"1\" == 1;
+            var g = nil != false;",
+        );
+
+        for name in ["a", "b", "c", "d", "g"] {
+            assert_eq!(
+                get_variable(&e, name),
+                RuntimeType::Boolean(Boolean::True),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(get_variable(&e, "f"), RuntimeType::Boolean(Boolean::False));
+    }
+
+    #[test]
+    fn nan_is_not_equal_to_itself() {
+        assert_ne!(RuntimeType::Number(f64::NAN), RuntimeType::Number(f64::NAN));
+    }
+
+    #[test]
+    fn typeof_names_every_type() {
+        let e = evaluate_source(
+            "fun f() { }
+            var number_type = typeof 1;
+            var string_type = typeof \"a\";
+            var boolean_type = typeof true;
+            var nil_type = typeof nil;
+            var array_type = typeof [1];
+            var function_type = typeof f;
+            var nested_type = typeof typeof 1;",
+        );
+
+        for (name, expected) in [
+            ("number_type", "number"),
+            ("string_type", "string"),
+            ("boolean_type", "boolean"),
+            ("nil_type", "nil"),
+            ("array_type", "array"),
+            ("function_type", "function"),
+            ("nested_type", "string"),
+        ] {
+            assert_eq!(
+                get_variable(&e, name),
+                RuntimeType::String(expected.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn truthiness_of_each_type() {
+        let function = match get_variable(&evaluate_source("fun f() { }"), "f") {
+            RuntimeType::Function(f) => RuntimeType::Function(f),
+            v => panic!("Expected a function, got {:?}", v),
+        };
+
+        assert!(!RuntimeType::Nil.is_truthy());
+        assert!(!RuntimeType::Boolean(Boolean::False).is_truthy());
+        assert!(RuntimeType::Boolean(Boolean::True).is_truthy());
+        assert!(RuntimeType::Number(0.0).is_truthy());
+        assert!(RuntimeType::Number(1.0).is_truthy());
+        assert!(RuntimeType::String(String::new()).is_truthy());
+        assert!(RuntimeType::Array(vec![]).is_truthy());
+        assert!(function.is_truthy());
+    }
+
+    #[test]
+    fn conditions_use_truthiness() {
+        let e = evaluate_source(
+            "var a = 0;
+            if (0) then { a = 1; }
+            var b = 0;
+            if (nil) then { b = 1; }
+            var one = false;
+            if (1) then { one = true; }
+            var empty_string = false;
+            if (\"\") then { empty_string = true; }
+            var c = 3;
+            var count = 0;
+            fun dec() { c = c - 1; if (c == 0) then { return nil; } return c; }
+            for (dec()) do { count = count + 1; }",
+        );
+
+        assert_eq!(get_variable(&e, "a"), RuntimeType::Integer(1));
+        assert_eq!(get_variable(&e, "b"), RuntimeType::Integer(0));
+        assert_eq!(get_variable(&e, "one"), RuntimeType::Boolean(Boolean::True));
+        assert_eq!(
+            get_variable(&e, "empty_string"),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        assert_eq!(get_variable(&e, "count"), RuntimeType::Integer(2));
+    }
+
+    /// A writer which can still be read from after it has been given to the
+    /// evaluator.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Operands are evaluated left to right, so their side effects happen in
+    /// the order they are written.
+    #[test]
+    fn operands_evaluate_left_to_right() {
+        let buffer = SharedBuffer::default();
+        let program = parse_source(
+            "fun lhs() { log \"A\"; return 1; }
+            fun rhs() { log \"B\"; return 2; }
+            var sum = lhs() + rhs();
+            var less = lhs() < rhs();
+            var both = [lhs(), rhs()][lhs()];
+            log lhs(), rhs();",
+        );
+
+        let mut e = Evaluator::new(program, 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.0.take()).unwrap(),
+            "A\nB\nA\nB\nA\nB\nA\nA\nB\n1 2\n"
+        );
+        assert_eq!(get_variable(&e, "sum"), RuntimeType::Integer(3));
+        assert_eq!(get_variable(&e, "both"), RuntimeType::Integer(2));
+    }
+
+    #[test]
+    fn expression_statements_are_only_echoed_when_asked() {
+        for (echo, expected) in [(false, ""), (true, "[expr_stmt] 2\n")] {
+            let buffer = SharedBuffer::default();
+
+            let mut e = Evaluator::new(parse_source("1 + 1; len(\"a\");"), 0);
+            e.set_output(Box::new(buffer.clone()));
+            e.set_echo_expressions(echo);
+            e.evaluate_program().unwrap();
+
+            assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn output_can_be_captured() {
+        let buffer = SharedBuffer::default();
+
+        let mut e = Evaluator::new(parse_source("log 1, \"two\"; log [3];"), 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "1 two\n[3]\n");
+    }
+
+    #[test]
+    fn and_and_or_combine_booleans() {
+        let mut e = Evaluator::new(vec![], 0);
+
+        assert_eq!(
+            e.eval_str("true and false;").unwrap(),
+            RuntimeType::Boolean(Boolean::False)
+        );
+        assert_eq!(
+            e.eval_str("false or true;").unwrap(),
+            RuntimeType::Boolean(Boolean::True)
+        );
+        // `and` binds tighter, so this is `(false and false) or true`.
+        assert_eq!(
+            e.eval_str("false and false or true;").unwrap(),
+            RuntimeType::Boolean(Boolean::True)
+        );
+    }
+
+    #[test]
+    fn trailing_commas_are_ignored() {
+        let buffer = SharedBuffer::default();
+
+        let mut e = Evaluator::new(parse_source("log 1, 2,; log len([1, 2,]);"), 0);
+        e.set_output(Box::new(buffer.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "1 2\n2\n");
+    }
+
+    #[test]
+    fn read_line_reads_from_the_input() {
+        let mut e = Evaluator::new(vec![], 0);
+        e.set_input(Box::new(std::io::Cursor::new("hello\r\nworld\n")));
+
+        assert_eq!(
+            e.eval_str("read_line();").unwrap(),
+            RuntimeType::String("hello".to_string())
+        );
+        assert_eq!(
+            e.eval_str("read_line();").unwrap(),
+            RuntimeType::String("world".to_string())
+        );
+        assert_eq!(e.eval_str("read_line();").unwrap(), RuntimeType::Nil);
+    }
+
+    #[test]
+    fn logerr_writes_to_the_error_output() {
+        let output = SharedBuffer::default();
+        let error_output = SharedBuffer::default();
+
+        let mut e = Evaluator::new(parse_source("log 1; logerr \"oops\", 2;"), 0);
+        e.set_output(Box::new(output.clone()));
+        e.set_error_output(Box::new(error_output.clone()));
+        e.evaluate_program().unwrap();
+
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "1\n");
+        assert_eq!(
+            String::from_utf8(error_output.0.take()).unwrap(),
+            "oops 2\n"
+        );
+    }
+}
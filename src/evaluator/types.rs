@@ -1,22 +1,215 @@
-use reef_syntax::common::*;
-use std::collections::HashMap;
+use super::limits::Resource;
+use reef_core::{lex::ScannerError, parse::ParserError};
+use reef_syntax::{ast::Stmt, common::*};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Display, Formatter, Result as FmtRes};
+use std::rc::Rc;
 
+/// Errors which can happen while a program is being evaluated. These stop the
+/// program and are reported back to whoever started the evaluator.
 #[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// The right hand side of a division or modulo was zero.
+    DivisionByZero,
+    /// An index was outside of the collection being indexed.
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    /// A map was looked up with a key it doesn't have.
+    MissingKey(String),
+    /// The program went over one of the evaluator's resource limits.
+    ResourceExhausted(Resource),
+    /// `assert` was called with a falsy condition, along with the message it
+    /// was given if there was one.
+    AssertionFailed(Option<String>),
+    /// A variable declared with `const` was reassigned. Holds its name.
+    AssignToConst(String),
+    /// An operator was used on values of types it doesn't work with, like
+    /// `true < false`. Holds the type names of both sides.
+    TypeMismatch {
+        operator: ComparisonOperator,
+        lhs: &'static str,
+        rhs: &'static str,
+    },
+    Generic(String),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        match self {
+            Self::DivisionByZero => write!(f, "Attempt to divide by zero"),
+            Self::IndexOutOfBounds { index, length } => write!(
+                f,
+                "Index {} is out of bounds for a collection of length {}",
+                index, length
+            ),
+            Self::MissingKey(key) => write!(f, "The map has no key \"{}\"", key),
+            Self::ResourceExhausted(resource) => {
+                write!(f, "The program went over its {} limit", resource)
+            }
+            Self::AssertionFailed(None) => write!(f, "Assertion failed"),
+            Self::AssertionFailed(Some(message)) => write!(f, "Assertion failed: {}", message),
+            Self::AssignToConst(name) => write!(
+                f,
+                "Cannot reassign \"{}\" because it was declared with `const`",
+                name
+            ),
+            Self::TypeMismatch { operator, lhs, rhs } => write!(
+                f,
+                "Cannot use `{}` on a {} and a {}, only numbers can be ordered",
+                operator, lhs, rhs
+            ),
+            Self::Generic(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Anything that can go wrong when running source code from start to end.
+#[derive(Debug)]
+pub enum ReefError {
+    Scanner(ScannerError),
+    Parser(ParserError),
+    Runtime(RuntimeError),
+}
+
+impl Display for ReefError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        match self {
+            Self::Scanner(err) => write!(f, "Syntax error: {}", err),
+            Self::Parser(err) => write!(f, "{}", err),
+            Self::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ScannerError> for ReefError {
+    fn from(err: ScannerError) -> Self {
+        Self::Scanner(err)
+    }
+}
+
+impl From<ParserError> for ReefError {
+    fn from(err: ParserError) -> Self {
+        Self::Parser(err)
+    }
+}
+
+impl From<RuntimeError> for ReefError {
+    fn from(err: RuntimeError) -> Self {
+        Self::Runtime(err)
+    }
+}
+
+/// A value a reef program can work with. Values are equal when they are the
+/// same variant holding equal values, so values of different types are never
+/// equal and `nil == nil`. The exception is numbers: an integer and a float
+/// are equal when they have the same value, so `5 == 5.0`. Floats follow
+/// IEEE 754, meaning NaN isn't equal to anything, including itself.
+#[derive(Debug, Clone)]
 pub enum RuntimeType {
+    /// A floating point number, like `5.0` or the result of `5.0 / 2`.
     Number(f64),
+    /// A whole number, like `5` or the result of `5 / 2`. See
+    /// `Evaluator::evaluate_binary_expression` for when integers turn into
+    /// floats.
+    Integer(i64),
     String(String),
     Boolean(Boolean),
+    Array(Vec<RuntimeType>),
+    /// A map from string keys to values, kept in key order.
+    Map(BTreeMap<String, RuntimeType>),
+    Function(Rc<Function>),
+    /// A value of a declared struct type, holding every field it declares.
+    Struct {
+        name: String,
+        fields: BTreeMap<String, RuntimeType>,
+    },
+    /// The language's `nil`, such as a variable declared without a value.
+    Nil,
+    /// No value at all, for statements which don't produce one. Reef code
+    /// can't get hold of this, unlike `Nil`.
     None,
 }
 
+impl PartialEq for RuntimeType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Integer(i), Self::Number(n)) | (Self::Number(n), Self::Integer(i)) => {
+                *i as f64 == *n
+            }
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Boolean(a), Self::Boolean(b)) => a == b,
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::Function(a), Self::Function(b)) => a == b,
+            (
+                Self::Struct { name, fields },
+                Self::Struct {
+                    name: other_name,
+                    fields: other_fields,
+                },
+            ) => name == other_name && fields == other_fields,
+            (Self::Nil, Self::Nil) | (Self::None, Self::None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A function declared in reef code. It keeps hold of the scope it was
+/// declared in, so its body can see the variables around the declaration.
+/// The function's own name is declared in that scope too, which is what lets
+/// it call itself recursively.
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Vec<Stmt>,
+    pub closure: Rc<RefCell<Scope>>,
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// The closure isn't printed, since the function usually lives in that scope
+/// and printing it would never end.
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
+        write!(f, "Function<{}({})>", self.name, self.parameters.join(", "))
+    }
+}
+
+/// Tells the evaluator how to carry on after a statement has run. `Return`
+/// unwinds out of every enclosing block, carrying the returned value with it.
+/// `Break` and `Continue` unwind out of blocks until they reach the innermost
+/// loop, which then stops or moves on to its next iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlFlow {
+    Normal,
+    Return(RuntimeType),
+    Break,
+    Continue,
+}
+
+/// Holds the variables declared in one part of the program. Lookups and
+/// reassignments fall back to the parent scope when a variable isn't declared
+/// in this one.
 #[derive(Debug)]
-pub struct Scope<'a> {
+pub struct Scope {
     variables: HashMap<String, RuntimeType>,
-    parent: Option<&'a mut Scope<'a>>,
+    /// The names of the variables in `variables` which were declared with
+    /// `const`, and so can't be reassigned.
+    constants: HashSet<String>,
+    parent: Option<Rc<RefCell<Scope>>>,
 }
 
-impl<'a> Display for Scope<'a> {
+impl Display for Scope {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
         write!(
             f,
@@ -26,41 +219,199 @@ impl<'a> Display for Scope<'a> {
     }
 }
 
-impl<'a> Scope<'a> {
-    pub fn new(parent: Option<&'a mut Scope<'a>>) -> Self {
+impl Scope {
+    pub fn new(parent: Option<Rc<RefCell<Scope>>>) -> Self {
         Self {
             variables: HashMap::new(),
-            parent: parent,
+            constants: HashSet::new(),
+            parent,
         }
     }
 
-    pub fn get_variable(&self, name: &str) -> RuntimeType {
+    pub fn get_variable(&self, name: &str) -> Result<RuntimeType, RuntimeError> {
         let v = self.variables.get(name);
 
         match v {
-            Some(v) => v.clone(),
+            Some(v) => Ok(v.clone()),
             None => match &self.parent {
-                Some(parent) => parent.get_variable(name),
-                None => panic!("No variable called {} exists", name),
+                Some(parent) => parent.borrow().get_variable(name),
+                None => Err(RuntimeError::Generic(format!(
+                    "No variable called {} exists",
+                    name
+                ))),
             },
         }
     }
 
-    pub fn set_variable(&mut self, name: &str, value: RuntimeType) -> RuntimeType {
+    /// A copy of the variables declared in this scope, not including its
+    /// parents, sorted by name.
+    #[allow(unused)]
+    pub fn variables(&self) -> Vec<(String, RuntimeType)> {
+        let variables: BTreeMap<String, RuntimeType> = self.variables.clone().into_iter().collect();
+        variables.into_iter().collect()
+    }
+
+    /// A copy of every variable that can be seen from this scope, including
+    /// those in its parents, sorted by name. A variable shadowing one in a
+    /// parent scope hides the parent's variable.
+    pub fn visible_variables(&self) -> Vec<(String, RuntimeType)> {
+        let mut variables: BTreeMap<String, RuntimeType> = match &self.parent {
+            Some(parent) => parent.borrow().visible_variables().into_iter().collect(),
+            None => BTreeMap::new(),
+        };
+
+        variables.extend(self.variables.clone());
+        variables.into_iter().collect()
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: RuntimeType) -> Result<(), RuntimeError> {
         if self.variables.contains_key(name) {
-            panic!("Variable named {name} already exists. Did you mean to reassign it?")
+            Err(RuntimeError::Generic(format!(
+                "Variable named {name} already exists. Did you mean to reassign it?"
+            )))
         } else {
             self.variables.insert(name.to_string(), value);
-            RuntimeType::None
+            Ok(())
         }
     }
 
-    pub fn reassign_variable(&mut self, name: &str, value: RuntimeType) -> RuntimeType {
-        if self.variables.contains_key(name) {
-            self.variables.insert(name.to_string(), value);
-            RuntimeType::None
+    /// Declares `name` like `set_variable`, but as a constant which can never
+    /// be reassigned.
+    pub fn set_constant(&mut self, name: &str, value: RuntimeType) -> Result<(), RuntimeError> {
+        self.set_variable(name, value)?;
+        self.constants.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Changes the value of `name` in whichever scope declared it. If no scope
+    /// did, the error suggests declaring it, or a variable with a similar name
+    /// in case of a typo. Constants can't be reassigned.
+    pub fn reassign_variable(
+        &mut self,
+        name: &str,
+        value: RuntimeType,
+    ) -> Result<(), RuntimeError> {
+        if self.try_reassign_variable(name, value)? {
+            return Ok(());
+        }
+
+        let mut message = format!(
+            "Attempt to reassign variable \"{name}\" which doesn't exist. Declare it first with `var {name} = ...;`."
+        );
+        if let Some(similar) = self.similar_variable_name(name) {
+            message.push_str(&format!(" Did you mean \"{similar}\"?"));
+        }
+
+        Err(RuntimeError::Generic(message))
+    }
+
+    /// Reassigns `name` if this scope or one of its parents has it, returning
+    /// whether it did.
+    fn try_reassign_variable(
+        &mut self,
+        name: &str,
+        value: RuntimeType,
+    ) -> Result<bool, RuntimeError> {
+        if self.constants.contains(name) {
+            Err(RuntimeError::AssignToConst(name.to_string()))
+        } else if let Some(variable) = self.variables.get_mut(name) {
+            *variable = value;
+            Ok(true)
+        } else if let Some(parent) = &self.parent {
+            parent.borrow_mut().try_reassign_variable(name, value)
         } else {
-            panic!("Attempt to reassign variable \"{name}\" which doesn't exist.")
+            Ok(false)
+        }
+    }
+
+    /// The visible variable closest to `name` by edit distance, as long as it
+    /// is close enough to likely be a typo (about one edit per three chars).
+    fn similar_variable_name(&self, name: &str) -> Option<String> {
+        let max_distance = name.chars().count() / 3;
+        let mut names = vec![];
+        self.collect_variable_names(&mut names);
+
+        names
+            .into_iter()
+            .map(|candidate| (edit_distance(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Pushes the name of every variable in this scope and its parents.
+    fn collect_variable_names(&self, names: &mut Vec<String>) {
+        names.extend(self.variables.keys().cloned());
+
+        if let Some(parent) = &self.parent {
+            parent.borrow().collect_variable_names(names);
+        }
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`: how many single character
+/// insertions, deletions or substitutions it takes to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+impl RuntimeType {
+    /// Whether the value counts as true where a condition is expected, such
+    /// as in `if`, `for`, `and`, `or` and `not`. Like Lua, only `nil` and
+    /// `false` are falsy, so `0`, `""` and `[]` are all truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Nil | Self::None | Self::Boolean(Boolean::False))
+    }
+
+    /// The value as an `f64` if it is a number of either kind.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            Self::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// The value as an `i64` if it is a whole number of either kind.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            Self::Number(n)
+                if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 =>
+            {
+                Some(*n as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// The name of the value's type, as given by `typeof`. Integers and floats
+    /// are both numbers.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) | Self::Integer(_) => "number",
+            Self::String(_) => "string",
+            Self::Boolean(_) => "boolean",
+            Self::Array(_) => "array",
+            Self::Map(_) => "map",
+            Self::Function(_) => "function",
+            Self::Struct { .. } => "struct",
+            Self::Nil => "nil",
+            Self::None => "none",
         }
     }
 }
@@ -68,8 +419,10 @@ impl<'a> Scope<'a> {
 impl Display for RuntimeType {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtRes {
         match self {
+            Self::Nil => write!(f, "nil")?,
             Self::None => write!(f, "None")?,
-            Self::Number(number) => write!(f, "{}", number)?,
+            Self::Number(number) => write!(f, "{}", format_number(*number))?,
+            Self::Integer(integer) => write!(f, "{}", integer)?,
             Self::String(string) => write!(f, "{}", string)?,
             Self::Boolean(boolean) => write!(
                 f,
@@ -79,8 +432,62 @@ impl Display for RuntimeType {
                     Boolean::False => "false",
                 }
             )?,
+            Self::Function(function) => write!(f, "<fun {}>", function.name)?,
+            Self::Struct { name, fields } => {
+                write!(f, "{} {{", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}: {}", field, value)?;
+                }
+                write!(f, " }}")?;
+            }
+            Self::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")?;
+            }
+            Self::Map(entries) if entries.is_empty() => write!(f, "{{}}")?,
+            Self::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {} = {}", key, value)?;
+                }
+                write!(f, " }}")?;
+            }
         }
 
         Ok(())
     }
 }
+
+/// The most decimal places a number is displayed with.
+const MAX_DECIMAL_PLACES: usize = 10;
+
+/// Formats a number for display. Whole numbers are shown without a decimal
+/// point, and anything else is rounded to at most `MAX_DECIMAL_PLACES` decimal
+/// places with any trailing zeros removed. This hides floating point noise,
+/// so `0.1 + 0.2` is shown as `0.3` and `1.0 / 3` as `0.3333333333`.
+fn format_number(number: f64) -> String {
+    if !number.is_finite() {
+        return format!("{}", number);
+    }
+
+    let formatted = format!("{:.*}", MAX_DECIMAL_PLACES, number);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    match trimmed {
+        // Tiny negative numbers round to "-0", which looks odd.
+        "-0" => String::from("0"),
+        _ => String::from(trimmed),
+    }
+}
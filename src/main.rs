@@ -4,24 +4,72 @@
 */
 
 use clap::Parser as ClapParser;
+use colored::Colorize;
 use reef_core::lex;
 use reef_core::parse;
-use reef_syntax::token::TokenDisplay;
-use std::io::Write;
-use std::{fmt::Display, fs, io, path};
+use reef_syntax::json;
+use reef_syntax::token::{Span, TokenDisplay};
+use reef_syntax::tree;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::time::{Duration, Instant};
+use std::{env, fmt::Display, fs, io, path, process};
 
 mod evaluator;
 use evaluator::evaluator as eval;
+use evaluator::limits::ResourceLimits;
+use evaluator::types::{ReefError, RuntimeType};
 
 const LEXER_DEBUG_FILE: &str = "reef_lexer.log";
 const PARSER_DEBUG_FILE: &str = "reef_parser.log";
 
 fn main() {
     let args = Args::parse();
+    apply_color_choice(if args.no_color {
+        ColorChoice::Never
+    } else {
+        args.color
+    });
 
-    match &args.path {
-        Some(path) => evaluate_file(&args, path.clone()),
-        None => repl(&args),
+    let result = match (&args.eval, args.paths.is_empty()) {
+        (Some(source_code), _) => run(source_code, &args),
+        (None, false) => evaluate_files(&args),
+        (None, true) => {
+            // Piped input (e.g. `cat script.reef | interpreter`) isn't a
+            // terminal, so it's run as a whole program instead of as a REPL.
+            if io::stdin().is_terminal() {
+                repl(&args);
+                Ok(())
+            } else {
+                evaluate_stdin(&args)
+            }
+        }
+    };
+
+    if let Err(failure) = result {
+        process::exit(failure.exit_code());
+    }
+}
+
+/// Why running some source code failed. The error has already been printed
+/// by the time one of these is returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunFailure {
+    /// The source code couldn't be parsed, so none of it was run.
+    Syntax,
+    /// The program stopped because of an error while it was running.
+    Runtime,
+    /// The source code file couldn't be read.
+    Unreadable,
+}
+
+impl RunFailure {
+    /// The status code the interpreter exits with for this failure.
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::Syntax => 65,
+            Self::Runtime => 70,
+            Self::Unreadable => 66,
+        }
     }
 }
 
@@ -30,8 +78,29 @@ fn main() {
 #[derive(ClapParser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short = 'f', long = "file", default_value = None, help = "The file with the source code inside")]
-    path: Option<path::PathBuf>,
+    #[arg(short = 'f', long = "file", num_args = 1.., help = "The file(s) with the source code inside")]
+    paths: Vec<path::PathBuf>,
+
+    #[arg(
+        long = "keep-going",
+        overrides_with = "fail_fast",
+        help = "Carries on running the rest of the files after one of them fails"
+    )]
+    keep_going: bool,
+
+    #[arg(
+        long = "fail-fast",
+        overrides_with = "keep_going",
+        help = "Stops running files as soon as one of them fails (the default)"
+    )]
+    fail_fast: bool,
+
+    #[arg(
+        short = 'e',
+        long = "eval",
+        help = "Source code to run directly, instead of reading it from a file"
+    )]
+    eval: Option<String>,
 
     #[arg(
         short = 'd',
@@ -40,79 +109,420 @@ struct Args {
         help = "Activates debug features"
     )]
     debug: u8,
+
+    #[arg(
+        long = "max-steps",
+        help = "Stops the program with an error once it has run this many statements and loop iterations"
+    )]
+    max_steps: Option<u64>,
+
+    #[arg(
+        long = "max-output-bytes",
+        help = "Stops the program with an error once it has written this many bytes of output"
+    )]
+    max_output_bytes: Option<usize>,
+
+    #[arg(
+        long = "permissive",
+        help = "Allows booleans to be used as numbers (1 and 0) in arithmetic"
+    )]
+    permissive: bool,
+
+    #[arg(
+        long = "color",
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        help = "When to use colors in the output"
+    )]
+    color: ColorChoice,
+
+    #[arg(
+        long = "no-color",
+        help = "Never uses colors in the output. Same as --color=never, and takes priority over --color"
+    )]
+    no_color: bool,
+
+    #[arg(
+        long = "time",
+        help = "Prints how long scanning, parsing and evaluating took to stderr"
+    )]
+    time: bool,
+
+    #[arg(
+        long = "check",
+        help = "Only checks the code for syntax errors, without running it"
+    )]
+    check: bool,
+
+    #[arg(
+        long = "tokens",
+        help = "Prints the scanned tokens instead of parsing and running the code"
+    )]
+    tokens: bool,
+
+    #[arg(
+        long = "ast",
+        value_enum,
+        help = "Prints the parsed program in the given format instead of running it"
+    )]
+    ast: Option<AstFormat>,
+}
+
+/// The formats the AST can be printed in with `--ast`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum AstFormat {
+    Json,
+    Tree,
+}
+
+/// The values which can be passed to `--color`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ColorChoice {
+    /// Use colors only when stdout is a terminal.
+    Auto,
+    Always,
+    Never,
 }
 
+/// Forces colors on or off depending on `choice`. For `auto`, colors are
+/// turned off when stdout isn't a terminal (e.g. it's piped into a file) or
+/// when the `NO_COLOR` environment variable is set to anything other than an
+/// empty string, and otherwise `colored` decides based on the environment.
+fn apply_color_choice(choice: ColorChoice) {
+    match choice {
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+        ColorChoice::Auto => {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+            if no_color || !io::stdout().is_terminal() {
+                colored::control::set_override(false);
+            }
+        }
+    }
+}
+
+/// Reads entries from stdin and runs them one at a time. Every entry is run
+/// by the same evaluator, so variables and functions declared by one entry
+/// can be used by the entries after it.
 fn repl(args: &Args) {
+    let mut evaluator = repl_evaluator(args);
+
     println!("/// You are in repl mode. Type 'EXIT' to exit. \\\\\\");
     loop {
-        print!("-> ");
-        io::stdout().flush().expect("Stdout flush failed");
+        // Stdin is only locked while reading, since `read_line()` in the
+        // code being run reads from it too.
+        let Some(buf) = read_entry(&mut io::stdin().lock(), &mut io::stdout()) else {
+            // Ctrl-D leaves the cursor after the prompt.
+            println!();
+            break;
+        };
 
-        let mut buf = String::new();
-        io::stdin()
-            .read_line(&mut buf)
+        match buf.as_str().trim() {
+            "EXIT" => break,
+            // These only print the code rather than running it, so there is
+            // no state to keep between entries.
+            _ if args.tokens || args.check || args.ast.is_some() => {
+                let _ = run(&buf, args);
+            }
+            _ => eval_entry(&mut evaluator, &buf),
+        }
+    }
+
+    println!("Goodbye!");
+}
+
+/// Creates the evaluator the REPL runs every entry with.
+fn repl_evaluator(args: &Args) -> eval::Evaluator {
+    let mut evaluator = eval::Evaluator::new_with_limits(vec![], args.debug, resource_limits(args));
+    evaluator.set_permissive(args.permissive);
+    evaluator
+}
+
+/// Runs one REPL entry and echoes its value. Nothing is echoed for entries
+/// without a value, such as declarations, or whose value is `nil`, such as a
+/// call to a function which doesn't return anything. Errors are printed, and
+/// the REPL carries on.
+fn eval_entry(evaluator: &mut eval::Evaluator, source_code: &str) {
+    let result = evaluator
+        .eval_str(source_code)
+        .and_then(|value| match value {
+            RuntimeType::None | RuntimeType::Nil => Ok(()),
+            value => Ok(evaluator.echo(value)?),
+        });
+
+    if let Err(err) = result {
+        print_error(source_code, &err);
+    }
+}
+
+/// Reads one entry for the REPL from `input`. Lines are read until they make
+/// up whole statements, so a statement can be split over several lines. The
+/// first line is prompted with `-> ` and the lines continuing it with `.. `.
+/// Returns `None` once the input has ended (e.g. with Ctrl-D) and there is
+/// nothing left to run.
+fn read_entry(input: &mut impl BufRead, output: &mut impl Write) -> Option<String> {
+    let mut entry = String::new();
+
+    loop {
+        let prompt = if entry.is_empty() { "-> " } else { ".. " };
+        write!(output, "{}", prompt).expect("Stdout write failed");
+        output.flush().expect("Stdout flush failed");
+
+        let read = input
+            .read_line(&mut entry)
             .expect("Failed to read from stdin");
 
-        match buf.as_str().trim() {
-            "EXIT" => panic!("Quit program"),
-            _ => run(&buf, args.debug),
+        // At the end of the input there is nothing left to wait for, but an
+        // unfinished entry is still run so its error is shown.
+        if read == 0 && entry.is_empty() {
+            return None;
+        }
+        if read == 0 || entry.trim() == "EXIT" || !is_incomplete(&entry) {
+            return Some(entry);
         }
     }
 }
 
-fn evaluate_file(args: &Args, path: path::PathBuf) {
-    let source_code = fs::read_to_string(path).expect("Failed to read source code from file.");
+/// Whether `source_code` ends part way through a statement, rather than being
+/// complete or having a syntax error.
+fn is_incomplete(source_code: &str) -> bool {
+    match reef_core::parse_to_ast(source_code) {
+        Err(err) => err.is_incomplete(),
+        Ok(_) => false,
+    }
+}
 
-    run(&source_code, args.debug);
+/// Runs every file passed with `-f` in order, each with its own evaluator.
+/// Stops at the first file that fails, unless `--keep-going` was passed, in
+/// which case every file is run and the ones that failed are listed at the end.
+fn evaluate_files(args: &Args) -> Result<(), RunFailure> {
+    let mut failures: Vec<(&path::PathBuf, RunFailure)> = vec![];
+
+    for path in &args.paths {
+        if let Err(failure) = evaluate_file(args, path.clone()) {
+            if !args.keep_going {
+                return Err(failure);
+            }
+
+            failures.push((path, failure));
+        }
+    }
+
+    match failures.first() {
+        Some(&(_, first_failure)) => {
+            eprintln!(
+                "{}",
+                format!(
+                    "[error] {} of {} files failed:",
+                    failures.len(),
+                    args.paths.len()
+                )
+                .bright_red()
+            );
+            for (path, _) in &failures {
+                eprintln!("{}", format!("  {}", path.display()).bright_red());
+            }
+
+            Err(first_failure)
+        }
+        None => Ok(()),
+    }
+}
+
+fn evaluate_file(args: &Args, path: path::PathBuf) -> Result<(), RunFailure> {
+    let source_code = match fs::read_to_string(&path) {
+        Ok(source_code) => source_code,
+        Err(err) => {
+            eprintln!(
+                "{}",
+                format!("[error] Couldn't read {}: {}", path.display(), err).bright_red()
+            );
+            return Err(RunFailure::Unreadable);
+        }
+    };
+
+    run(&source_code, args)
+}
+
+fn evaluate_stdin(args: &Args) -> Result<(), RunFailure> {
+    let mut source_code = String::new();
+    io::stdin()
+        .read_to_string(&mut source_code)
+        .expect("Failed to read source code from stdin.");
+
+    run(&source_code, args)
 }
 
-fn run(source_code: &str, debug: u8) {
+/// Scans, parses and evaluates `source_code`. Nothing is evaluated if the
+/// code fails to parse.
+fn run(source_code: &str, args: &Args) -> Result<(), RunFailure> {
+    let debug = args.debug;
     let mut scanner: lex::Scanner;
     let mut parser: parse::Parser;
     let mut evaluator: eval::Evaluator;
 
+    let mut timings: Vec<(&str, Duration)> = vec![];
+
+    let started = Instant::now();
     scanner = lex::Scanner::new(source_code, debug);
-    scanner.scan();
+    let scan_result = scanner.scan();
+    timings.push(("lex", started.elapsed()));
 
     if debug >= 1 {
         let _ = write_to_debug_file(
             path::Path::new(LEXER_DEBUG_FILE),
-            format!("{}", TokenDisplay(&scanner.tokens)),
+            format!("{}", TokenDisplay(&scanner.tokens, &scanner.spans)),
         );
         println!("[log] Wrote scanner output to {}", LEXER_DEBUG_FILE)
     }
 
-    parser = parse::Parser::new(scanner.tokens, debug);
+    if let Err(errors) = scan_result {
+        for err in errors {
+            print_error(source_code, &err.into());
+        }
+
+        return Err(RunFailure::Syntax);
+    }
+
+    if args.tokens {
+        print!("{}", TokenDisplay(&scanner.tokens, &scanner.spans));
+        print_timings(args, &timings);
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    parser = parse::Parser::new(scanner.tokens, scanner.spans, debug);
     let parse_result = parser.parse_all();
+    timings.push(("parse", started.elapsed()));
     match parse_result {
         Ok(_) => {
             if debug >= 1 {
                 let _ = write_to_debug_file(
                     path::Path::new(PARSER_DEBUG_FILE),
-                    format!("{:?}", parser.program),
+                    tree::program_to_tree(&parser.program),
                 );
                 println!("[log] Wrote parser output to {}", PARSER_DEBUG_FILE)
             }
         }
-        Err(err) => match err {
-            parse::ParserError::SyntaxError { position, message } => {
-                println!("Syntax error: at {}, {}", position, message)
-            }
-            parse::ParserError::CurrentIndexOutOfBounds(position) => {
-                println!("Attempt to index out of bounds. Index at {}", position)
-            }
-            parse::ParserError::UnknownToken { position } => {
-                println!("Encountered an unknown token at position {}", position)
-            }
-        },
+        Err(err) => {
+            print_error(source_code, &err.into());
+            return Err(RunFailure::Syntax);
+        }
     };
 
+    for warning in &parser.warnings {
+        eprintln!("{}", format!("[warning] {}", warning).yellow());
+    }
+
     // dbg!(&parser.program);
 
-    evaluator = eval::Evaluator::new(parser.program, debug);
-    evaluator.evaluate_program();
+    if args.check {
+        print_timings(args, &timings);
+        return Ok(());
+    }
+
+    match args.ast {
+        Some(AstFormat::Json) => {
+            println!("{}", json::program_to_json(&parser.program));
+            print_timings(args, &timings);
+            return Ok(());
+        }
+        Some(AstFormat::Tree) => {
+            print!("{}", tree::program_to_tree(&parser.program));
+            print_timings(args, &timings);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    evaluator = eval::Evaluator::new_with_limits(parser.program, debug, resource_limits(args));
+    evaluator.set_permissive(args.permissive);
+
+    let started = Instant::now();
+    let result = evaluator.evaluate_program();
+    timings.push(("eval", started.elapsed()));
+    print_timings(args, &timings);
+
+    if let Err(err) = result {
+        print_error(source_code, &err.into());
+        return Err(RunFailure::Runtime);
+    }
 
     // println!("{}", evaluator.get_main_scope());
+
+    Ok(())
+}
+
+/// The limits set on the command line. Anything not set is unlimited.
+fn resource_limits(args: &Args) -> ResourceLimits {
+    ResourceLimits {
+        max_steps: args.max_steps,
+        max_output_bytes: args.max_output_bytes,
+        ..Default::default()
+    }
+}
+
+/// Prints `err`. Syntax errors go to stdout with a snippet of the code they
+/// are in, and runtime errors go to stderr.
+fn print_error(source_code: &str, err: &ReefError) {
+    let position = match err {
+        ReefError::Scanner(err) => Some(err.position()),
+        ReefError::Parser(err) => err.position(),
+        ReefError::Runtime(_) => {
+            eprintln!("{}", format!("[error] {}", err).bright_red());
+            return;
+        }
+    };
+
+    println!("{}", err);
+    if let Some(position) = position {
+        print!("{}", source_snippet(source_code, position));
+    }
+}
+
+/// Shows the line of `source_code` that `position` is on, with a `^` under
+/// its column, so errors can point at the code that caused them:
+///
+/// ```text
+/// 1 | var x = 1 log x;
+///   |           ^
+/// ```
+fn source_snippet(source_code: &str, position: Span) -> String {
+    let line_number = position.line.to_string();
+    let line = source_code
+        .lines()
+        .nth((position.line as usize).saturating_sub(1))
+        .unwrap_or("");
+    // Tabs are kept so the caret lines up however wide they are shown.
+    let padding: String = line
+        .chars()
+        .take((position.col as usize).saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!(
+        "{} | {}\n{} | {}^\n",
+        line_number,
+        line,
+        " ".repeat(line_number.len()),
+        padding
+    )
+}
+
+/// With `--time`, prints how long each phase that was run took to stderr, like
+/// `lex: 1.2ms, parse: 0.3ms, eval: 4.5ms`.
+fn print_timings(args: &Args, timings: &[(&str, Duration)]) {
+    if !args.time {
+        return;
+    }
+
+    let timings: Vec<String> = timings
+        .iter()
+        .map(|(phase, duration)| format!("{}: {:.1}ms", phase, duration.as_secs_f64() * 1000.0))
+        .collect();
+
+    eprintln!("{}", timings.join(", "));
 }
 
 fn write_to_debug_file<T: Display>(path: &path::Path, data: T) -> Result<(), String> {
@@ -123,3 +533,79 @@ fn write_to_debug_file<T: Display>(path: &path::Path, data: T) -> Result<(), Str
         Err(e) => Err(format!("{e}")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn repl_reads_until_the_statement_is_complete() {
+        let mut input = Cursor::new("var x =\n 5;\nlog x;\n");
+        let mut output = vec![];
+
+        assert_eq!(
+            read_entry(&mut input, &mut output).as_deref(),
+            Some("var x =\n 5;\n")
+        );
+        assert_eq!(
+            read_entry(&mut input, &mut output).as_deref(),
+            Some("log x;\n")
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "-> .. -> ");
+    }
+
+    #[test]
+    fn repl_entries_share_one_evaluator() {
+        let mut evaluator = repl_evaluator(&Args::parse_from(["interpreter"]));
+        let mut input = Cursor::new("var x =\n 5;\nx + 1;\n");
+        let mut output = vec![];
+
+        let declaration = read_entry(&mut input, &mut output).unwrap();
+        assert_eq!(evaluator.eval_str(&declaration).unwrap(), RuntimeType::None);
+
+        let expression = read_entry(&mut input, &mut output).unwrap();
+        assert_eq!(
+            evaluator.eval_str(&expression).unwrap(),
+            RuntimeType::Integer(6)
+        );
+    }
+
+    #[test]
+    fn repl_stops_at_the_end_of_the_input() {
+        let mut input = Cursor::new("var x =");
+        let mut output = vec![];
+
+        assert_eq!(
+            read_entry(&mut input, &mut output).as_deref(),
+            Some("var x =")
+        );
+        assert_eq!(read_entry(&mut input, &mut output), None);
+    }
+
+    #[test]
+    fn snippets_point_at_the_error_column() {
+        let source_code = "log 1;\n\tvar = 10;\n";
+        let position = Span {
+            line: 2,
+            col: 6,
+            start: 12,
+            end: 13,
+        };
+
+        assert_eq!(
+            source_snippet(source_code, position),
+            "2 | \tvar = 10;\n  | \t    ^\n"
+        );
+    }
+
+    #[test]
+    fn repl_only_waits_for_unfinished_code() {
+        assert!(is_incomplete("var x ="));
+        assert!(is_incomplete("fun f() {\n log 1;"));
+        assert!(is_incomplete("log (1 + 2"));
+        assert!(!is_incomplete("var x = 5; log x;"));
+        assert!(!is_incomplete("var = 5;"));
+        assert!(!is_incomplete(""));
+    }
+}
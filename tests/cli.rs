@@ -0,0 +1,361 @@
+/*
+    Integration tests which run the interpreter executable, the same way a
+    user would from the command line.
+*/
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::{env, fs};
+
+/// Writes `source_code` to a file in the temp directory and returns its path.
+/// `name` should be unique to each test so tests can run in parallel.
+fn write_source(name: &str, source_code: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("reef_cli_{}_{}.reef", std::process::id(), name));
+    fs::write(&path, source_code).expect("Failed to write test source file");
+    path
+}
+
+/// Runs the interpreter with `args` and returns everything it output.
+fn run_interpreter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .output()
+        .expect("Failed to run the interpreter")
+}
+
+fn stdout_of(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr_of(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn color_always_emits_ansi_codes() {
+    let path = write_source("color_always", "log 1 / 0;");
+    let output = run_interpreter(&["--color=always", "-f", path.to_str().unwrap()]);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\x1b["));
+}
+
+#[test]
+fn color_never_emits_no_ansi_codes() {
+    let path = write_source("color_never", "log 1 / 0;");
+    let output = run_interpreter(&["--color=never", "-f", path.to_str().unwrap()]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("divide by zero"));
+    assert!(!stderr.contains("\x1b["));
+}
+
+#[test]
+fn no_color_overrides_color_always() {
+    let output = run_interpreter(&["--color=always", "--no-color", "-e", "log 3 / 0;"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("divide by zero"));
+    assert!(!stderr.contains("\x1b["));
+}
+
+#[test]
+fn no_color_environment_variable_disables_colors() {
+    let output = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(["-e", "log 3 / 0;"])
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run the interpreter");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("divide by zero"));
+    assert!(!stderr.contains("\x1b["));
+}
+
+#[test]
+fn empty_and_comment_only_files_run_without_output() {
+    for (name, source_code) in [
+        ("empty", ""),
+        ("whitespace_only", "  \n\t\n"),
+        (
+            "comment_only",
+            "-- nothing to run\n--[[ still\nnothing ]]\n",
+        ),
+    ] {
+        let path = write_source(name, source_code);
+        let output = run_interpreter(&["--color=never", "-f", path.to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(0), "{}", name);
+        assert_eq!(stdout_of(&output), "", "{}", name);
+        assert!(output.stderr.is_empty(), "{}", name);
+    }
+}
+
+#[test]
+fn scripts_do_not_echo_bare_expressions() {
+    let path = write_source("bare_expression", "1 + 1;\n\"a\";\nlog 3;");
+    let output = run_interpreter(&["--color=never", "-f", path.to_str().unwrap()]);
+
+    assert_eq!(stdout_of(&output), "3\n");
+}
+
+#[test]
+fn call_statements_do_not_echo_their_return_value() {
+    let path = write_source(
+        "call_statement",
+        "fun foo() { log \"inside\"; return 5; }\nfoo();",
+    );
+    let output = run_interpreter(&["--color=never", "-f", path.to_str().unwrap()]);
+
+    assert_eq!(stdout_of(&output), "inside\n");
+}
+
+#[test]
+fn eval_runs_the_given_source() {
+    let output = run_interpreter(&["--color=never", "-e", "log 1 + 2;"]);
+
+    assert_eq!(stdout_of(&output), "3\n");
+}
+
+#[test]
+fn eval_takes_priority_over_file() {
+    let path = write_source("eval_priority", "log \"file\";");
+    let output = run_interpreter(&[
+        "--color=never",
+        "-f",
+        path.to_str().unwrap(),
+        "--eval",
+        "log \"eval\";",
+    ]);
+
+    assert_eq!(stdout_of(&output), "eval\n");
+}
+
+#[test]
+fn piped_stdin_runs_as_a_program() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .arg("--color=never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the interpreter");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"log 1;\nlog 2;\n")
+        .expect("Failed to write to stdin");
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(stdout_of(&output), "1\n2\n");
+}
+
+#[test]
+fn syntax_errors_exit_without_evaluating() {
+    let path = write_source("syntax_error", "log \"before\";\nvar = 10;");
+    let output = run_interpreter(&["--color=never", "-f", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(!stdout_of(&output).contains("before"));
+}
+
+#[test]
+fn missing_tokens_are_reported_without_a_backtrace() {
+    let output = run_interpreter(&["--color=never", "-e", "var x = 1 log x;"]);
+
+    assert_eq!(
+        stdout_of(&output),
+        "Syntax error: expected Delimiter(';') but found Keyword(\"log\") at line 1, column 11\n\
+         1 | var x = 1 log x;\n\
+        \x20 |           ^\n"
+    );
+}
+
+#[test]
+fn unknown_tokens_are_reported_once() {
+    let output = run_interpreter(&["--color=never", "-e", ") ;"]);
+
+    assert_eq!(
+        stdout_of(&output),
+        "Encountered an unknown token at line 1, column 1\n\
+         1 | ) ;\n\
+        \x20 | ^\n"
+    );
+}
+
+#[test]
+fn logerr_writes_to_stderr() {
+    let output = run_interpreter(&["--color=never", "-e", "log \"out\"; logerr \"err\";"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout_of(&output), "out\n");
+    assert_eq!(stderr_of(&output), "err\n");
+}
+
+#[test]
+fn time_reports_each_phase_on_stderr() {
+    let output = run_interpreter(&["--color=never", "--time", "-e", "log 1;"]);
+
+    assert_eq!(stdout_of(&output), "1\n");
+    let stderr = stderr_of(&output);
+    let timings: Vec<&str> = stderr.trim_end().split(", ").collect();
+    assert_eq!(timings.len(), 3, "{}", stderr);
+    for (timing, phase) in timings.iter().zip(["lex: ", "parse: ", "eval: "]) {
+        assert!(timing.starts_with(phase), "{}", stderr);
+        assert!(timing.ends_with("ms"), "{}", stderr);
+    }
+}
+
+#[test]
+fn check_parses_without_running() {
+    let valid = write_source("check_valid", "log \"ran\";\nvar x = 1 / 0;");
+    let output = run_interpreter(&["--color=never", "--check", "-f", valid.to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout_of(&output), "");
+
+    let invalid = write_source("check_invalid", "log \"ran\";\nvar = 1;");
+    let output = run_interpreter(&["--color=never", "--check", "-f", invalid.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(!stdout_of(&output).contains("ran"));
+}
+
+#[test]
+fn runtime_errors_exit_with_failure() {
+    let output = run_interpreter(&["--color=never", "-e", "log 1 / 0;"]);
+
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn failed_assertions_exit_with_failure() {
+    let output = run_interpreter(&["--color=never", "-e", "assert(1 == 2, \"maths\");"]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr_of(&output).contains("Assertion failed: maths"));
+}
+
+#[test]
+fn successful_runs_exit_with_success() {
+    let output = run_interpreter(&["--color=never", "-e", "log 1;"]);
+
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn log_separates_arguments_with_one_space() {
+    let output = run_interpreter(&["--color=never", "-e", "log \"\", \"x\"; log 1, \"\", 2;"]);
+
+    assert_eq!(stdout_of(&output), " x\n1  2\n");
+}
+
+#[test]
+fn ast_json_prints_the_program_without_running_it() {
+    let output = run_interpreter(&["--color=never", "--ast", "json", "-e", "log 1;"]);
+
+    assert_eq!(
+        stdout_of(&output),
+        "[{\"type\":\"LogStatement\",\"arguments\":[{\"type\":\"IntegerLiteral\",\"value\":1}]},{\"type\":\"EmptyStatement\"}]\n"
+    );
+}
+
+#[test]
+fn tokens_prints_the_token_stream_without_running_it() {
+    let expected =
+        "  1:1  Keyword(\"log\")\n  1:5  Number(\"1\")\n  1:6  Delimiter(';')\n  1:7  EndOfFile\n";
+
+    let output = run_interpreter(&["--tokens", "-e", "log 1;"]);
+    assert!(output.status.success());
+    assert_eq!(stdout_of(&output), expected);
+
+    let path = write_source("tokens", "log 1;");
+    let output = run_interpreter(&["--tokens", "-f", path.to_str().unwrap()]);
+    assert_eq!(stdout_of(&output), expected);
+}
+
+#[test]
+fn failing_file_stops_the_batch_by_default() {
+    let first = write_source("fail_fast_first", "log 1 / 0;");
+    let second = write_source("fail_fast_second", "log \"second\";");
+    let output = run_interpreter(&[
+        "--color=never",
+        "-f",
+        first.to_str().unwrap(),
+        second.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(!stdout_of(&output).contains("second"));
+}
+
+#[test]
+fn keep_going_runs_every_file_and_lists_failures() {
+    let first = write_source("keep_going_first", "log 1 / 0;");
+    let second = write_source("keep_going_second", "log \"second\";");
+    let output = run_interpreter(&[
+        "--color=never",
+        "--keep-going",
+        "-f",
+        first.to_str().unwrap(),
+        second.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout_of(&output), "second\n");
+
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("1 of 2 files failed"));
+    assert!(stderr.contains(first.to_str().unwrap()));
+    assert!(!stderr.contains(second.to_str().unwrap()));
+}
+
+#[test]
+fn keep_going_reports_unreadable_files_and_runs_the_rest() {
+    let missing = env::temp_dir().join(format!("reef_cli_{}_missing.reef", std::process::id()));
+    let second = write_source("unreadable_second", "log \"second\";");
+    let output = run_interpreter(&[
+        "--color=never",
+        "--keep-going",
+        "-f",
+        missing.to_str().unwrap(),
+        second.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(66));
+    assert_eq!(stdout_of(&output), "second\n");
+
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("Couldn't read"), "{}", stderr);
+    assert!(stderr.contains("1 of 2 files failed"), "{}", stderr);
+}
+
+#[test]
+fn output_cap_stops_infinite_logging() {
+    let output = run_interpreter(&[
+        "--color=never",
+        "--max-output-bytes",
+        "100",
+        "-e",
+        "for (true) do { log \"spam\"; }",
+    ]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout_of(&output), "spam\n".repeat(20));
+    assert!(stderr_of(&output).contains("output size limit"));
+}
+
+#[test]
+fn step_limit_stops_infinite_loops() {
+    let output = run_interpreter(&[
+        "--color=never",
+        "--max-steps",
+        "1000",
+        "-e",
+        "var i = 0; for (true) do { i = i + 1; }",
+    ]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr_of(&output).contains("step limit"));
+}